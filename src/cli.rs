@@ -3,7 +3,6 @@ use std::env;
 
 #[derive(Debug)]
 pub struct Arguments {
-    pub path: String,
     pub named: HashMap<String, String>,
     pub unnamed: Vec<String>,
 }
@@ -14,15 +13,16 @@ impl Arguments {
         let mut unnamed: Vec<String> = Vec::new();
 
         let mut iter = env::args();
-        let path = iter.next().unwrap();
+        // Skip the executable path.
+        iter.next();
         let mut next_arg = iter.next();
         while next_arg.is_some() {
             let arg = unsafe { next_arg.unwrap_unchecked() };
             if arg.starts_with('-') {
                 let key = arg.trim_start_matches('-');
                 if key == "h" || key == "help" {
-					print_help();
-					std::process::exit(0);
+                    print_help();
+                    std::process::exit(0);
                 }
                 let value = iter.next().expect("No value for optional key found!");
                 named.insert(key.to_string(), value);
@@ -33,21 +33,57 @@ impl Arguments {
             next_arg = iter.next();
         }
 
-        Self {
-            path,
-            named,
-            unnamed,
-        }
+        Self { named, unnamed }
     }
 }
 
 fn print_help() {
-	println!("{HELP}");
+    println!("{HELP}");
 }
 
 static HELP: &str = "Usage:
   pixel_editor --width 10 --height 10 <PATH>
-  	open PATH as a .ppt image of width and size
+  	open PATH as a .ppt, .png or .gif image or a .pxe project, width and height are only used
+  	when PATH doesn't exist yet, projects keep layers, frames, palette and selected color,
+  	images are flattened and only GIFs keep every frame
+  pixel_editor --resize 64x48 --anchor center <PATH>
+  	crop or pad PATH to the new size, keeping the anchor (top-left, top, top-right, left,
+  	center, right, bottom-left, bottom, bottom-right) in place, defaults to top-left
+  pixel_editor --format P6 <PATH>
+  	save PATH as P1, P2, P3 (ascii), P4, P5, P6 (binary) or P7 (pam with transparency),
+  	defaults to the format of the loaded file, new .pam files default to P7, ignored for .png
+  	and .gif
+  pixel_editor --maxval 65535 <PATH>
+  	save PATH with samples in 0..=maxval, above 255 binary formats use 16 bit samples
+  pixel_editor --undo-limit 16384 <PATH>
+  	KiB of memory used for undo history, ctrl+z undoes and ctrl+shift+z redoes a stroke
+  pixel_editor --palette colors.gpl <PATH>
+  	load the palette from a GIMP .gpl, Paint.NET .txt, JASC .pal or .hex list of RRGGBB colors,
+  	the palette is saved back on exit when colors were added or removed
+  pixel_editor --scale 10 --window 800x600 <PATH>
+  	starting zoom and size of the canvas view, the view defaults to the zoomed canvas
+  	but stays between 320x160 and 1024x768
+  pixel_editor --guide 16 <PATH>
+  	show a guide grid every 16 pixels (or e.g. 16x8 for wider tiles), shift+g toggles it
+  	and g toggles the pixel grid
+  pixel_editor --onion 2 --onion-past ff0000 --onion-future 0000ff <PATH>
+  	frames before and after the current one shown by the onion skin, toggled with i,
+  	and the colors they are tinted with, defaults to 1 frame, red and blue
+  pixel_editor --loop 3 --transparent ff00ff <PATH>
+  	how often a saved .gif repeats after playing once, or forever, defaults to forever for new
+  	files and the loaded count otherwise, and a color saved transparent along with pixels
+  	that are mostly transparent
+  pixel_editor --tags walk:1-4,jump:5-8 <PATH>
+  	name runs of frames, counted from 1, replacing the tags saved in a .pxe project
+  pixel_editor --sheet sheet.png --sheet-layout packed --columns 4 --padding 1 --extrude 1 <PATH>
+  	also save every frame to a sprite sheet, with frame rectangles, durations and tags in
+  	sheet.json like Aseprite's array format, grid layouts fit whole frames in rows of
+  	--columns cells, packed layouts trim frames to their opaque pixels, padding puts
+  	transparent pixels between frames and extrude repeats their edges outwards
+  pixel_editor --import-sheet sheet.png --cell 16x16 --margin 1 --spacing 2 <PATH>
+  	slice the cells of sheet.png, row by row, into the frames of PATH, which has to be a new
+  	file, margin is the border around the cells and spacing the gap between them,
+  	--empty-cells skip leaves out fully transparent cells, keep is the default
   pixel_editor --help, pixel_editor -h
   	print this help message
 ";
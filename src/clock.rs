@@ -2,7 +2,6 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 /// Keeps a consistent framerate.
-
 pub struct Clock {
     creation_time: Instant,
     past: [Duration; 2],
@@ -23,6 +22,11 @@ impl Clock {
         }
     }
 
+    /// Time between the last two updates.
+    pub fn delta(&self) -> Duration {
+        self.past[0]
+    }
+
     pub fn update(&mut self) {
         self.cycles += 1;
         let now = Instant::now();
@@ -30,7 +34,7 @@ impl Clock {
         self.lifetime = now.duration_since(self.creation_time);
         self.past[1] = self.past[0];
         self.past[0] = time_delta;
-        if self.cycles % 15 == 0 {
+        if self.cycles.is_multiple_of(15) {
             let fps = 1.0 / time_delta.as_secs_f32();
             println!(
                 "playtime: {:.2}, fps: {fps:.2}",
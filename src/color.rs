@@ -0,0 +1,306 @@
+use simple_pixels::rgb::RGBA8;
+
+/// Light and dark squares drawn behind transparent pixels.
+pub const CHECKER_LIGHT: RGBA8 = RGBA8::new(200, 200, 200, 255);
+pub const CHECKER_DARK: RGBA8 = RGBA8::new(150, 150, 150, 255);
+
+/// Composites `top` over an opaque `bottom` using the alpha of `top`.
+pub fn over(top: RGBA8, bottom: RGBA8) -> RGBA8 {
+    let alpha = u32::from(top.a);
+    let mix = |top: u8, bottom: u8| {
+        ((u32::from(top) * alpha + u32::from(bottom) * (255 - alpha) + 127) / 255) as u8
+    };
+    RGBA8::new(
+        mix(top.r, bottom.r),
+        mix(top.g, bottom.g),
+        mix(top.b, bottom.b),
+        255,
+    )
+}
+
+/// `color` with its red, green and blue halfway to those of `tint`, keeping its alpha.
+pub fn tint(color: RGBA8, tint: RGBA8) -> RGBA8 {
+    RGBA8::new(
+        u8::midpoint(color.r, tint.r),
+        u8::midpoint(color.g, tint.g),
+        u8::midpoint(color.b, tint.b),
+        color.a,
+    )
+}
+
+/// How a layer combines with the layers below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Add,
+    Screen,
+}
+
+impl BlendMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Normal => Self::Multiply,
+            Self::Multiply => Self::Add,
+            Self::Add => Self::Screen,
+            Self::Screen => Self::Normal,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Multiply => "multiply",
+            Self::Add => "add",
+            Self::Screen => "screen",
+        }
+    }
+
+    /// Channels in 0..=1.
+    fn mix(self, top: f32, bottom: f32) -> f32 {
+        match self {
+            Self::Normal => top,
+            Self::Multiply => top * bottom,
+            Self::Add => (top + bottom).min(1.0),
+            Self::Screen => top.mul_add(1.0 - bottom, bottom),
+        }
+    }
+}
+
+/// Composites `top` over `bottom` with the alpha of `top` scaled by `opacity`, where both
+/// are opaque the channels are mixed by `mode`.
+pub fn blend(top: RGBA8, bottom: RGBA8, mode: BlendMode, opacity: u8) -> RGBA8 {
+    let top_alpha = f32::from(top.a) * f32::from(opacity) / (255.0 * 255.0);
+    if top_alpha == 0.0 {
+        return bottom;
+    }
+    let bottom_alpha = f32::from(bottom.a) / 255.0;
+    let alpha = top_alpha + bottom_alpha * (1.0 - top_alpha);
+    let channel = |top: u8, bottom: u8| {
+        let (top, bottom) = (f32::from(top) / 255.0, f32::from(bottom) / 255.0);
+        let only_top = top_alpha * (1.0 - bottom_alpha) * top;
+        let both = top_alpha * bottom_alpha * mode.mix(top, bottom);
+        let only_bottom = (1.0 - top_alpha) * bottom_alpha * bottom;
+        ((only_top + both + only_bottom) / alpha * 255.0).round() as u8
+    };
+    RGBA8::new(
+        channel(top.r, bottom.r),
+        channel(top.g, bottom.g),
+        channel(top.b, bottom.b),
+        (alpha * 255.0).round() as u8,
+    )
+}
+
+/// Parses `#rrggbb` or `#rrggbbaa`, the `#` is optional.
+pub fn from_hex(hex: &str) -> Option<RGBA8> {
+    let hex = hex.trim().trim_start_matches('#');
+    let [a, b, c, d] = u32::from_str_radix(hex, 16).ok()?.to_be_bytes();
+    match hex.len() {
+        6 => Some(RGBA8::new(b, c, d, 255)),
+        8 => Some(RGBA8::new(a, b, c, d)),
+        _ => None,
+    }
+}
+
+/// Parses a hex color, or red, green, blue and optionally alpha separated by spaces or commas.
+pub fn parse_color(text: &str) -> Option<RGBA8> {
+    let channels: Vec<&str> = text
+        .split(|ch: char| ch == ',' || ch.is_whitespace())
+        .filter(|channel| !channel.is_empty())
+        .collect();
+    if channels.len() == 1 {
+        return from_hex(channels[0]);
+    }
+    let channels: Vec<u8> = channels
+        .iter()
+        .map(|channel| channel.parse().ok())
+        .collect::<Option<_>>()?;
+    match channels[..] {
+        [r, g, b] => Some(RGBA8::new(r, g, b, 255)),
+        [r, g, b, a] => Some(RGBA8::new(r, g, b, a)),
+        _ => None,
+    }
+}
+
+/// Channels the selection keys edit, alpha is always edited directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    #[default]
+    Rgb,
+    Hsv,
+    Hsl,
+}
+
+impl ColorMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Rgb => Self::Hsv,
+            Self::Hsv => Self::Hsl,
+            Self::Hsl => Self::Rgb,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rgb => "rgb",
+            Self::Hsv => "hsv",
+            Self::Hsl => "hsl",
+        }
+    }
+
+    pub fn channel_names(self) -> [char; 3] {
+        match self {
+            Self::Rgb => ['r', 'g', 'b'],
+            Self::Hsv => ['h', 's', 'v'],
+            Self::Hsl => ['h', 's', 'l'],
+        }
+    }
+
+    /// Red, green and blue, or hue in degrees with saturation and value or lightness in percent.
+    pub fn split(self, color: RGBA8) -> [f32; 3] {
+        let [r, g, b] = [color.r, color.g, color.b].map(|channel| f32::from(channel) / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        let hue = if delta == 0.0 {
+            0.0
+        } else if color.r >= color.g && color.r >= color.b {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if color.g >= color.b {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        match self {
+            Self::Rgb => [color.r, color.g, color.b].map(f32::from),
+            Self::Hsv => {
+                let saturation = if max == 0.0 { 0.0 } else { delta / max };
+                [hue, saturation * 100.0, max * 100.0]
+            }
+            Self::Hsl => {
+                let lightness = f32::midpoint(max, min);
+                let saturation = if delta == 0.0 {
+                    0.0
+                } else {
+                    delta / (1.0 - (max + min - 1.0).abs())
+                };
+                [hue, saturation * 100.0, lightness * 100.0]
+            }
+        }
+    }
+
+    pub fn join(self, [first, second, third]: [f32; 3], alpha: u8) -> RGBA8 {
+        // Value or lightness.
+        let (saturation, level) = (second / 100.0, third / 100.0);
+        let (chroma, max) = match self {
+            Self::Rgb => {
+                let [r, g, b] = [first, second, third].map(|channel| channel.round() as u8);
+                return RGBA8::new(r, g, b, alpha);
+            }
+            Self::Hsv => (level * saturation, level),
+            Self::Hsl => {
+                let chroma = (1.0 - level.mul_add(2.0, -1.0).abs()) * saturation;
+                (chroma, level + chroma / 2.0)
+            }
+        };
+        let hue = first / 60.0;
+        let x = chroma * (1.0 - (hue.rem_euclid(2.0) - 1.0).abs());
+        let rgb = match hue as u32 {
+            0 => [chroma, x, 0.0],
+            1 => [x, chroma, 0.0],
+            2 => [0.0, chroma, x],
+            3 => [0.0, x, chroma],
+            4 => [x, 0.0, chroma],
+            _ => [chroma, 0.0, x],
+        };
+        let min = max - chroma;
+        let [r, g, b] = rgb.map(|channel| ((channel + min) * 255.0).round() as u8);
+        RGBA8::new(r, g, b, alpha)
+    }
+
+    /// Moves one channel by `step`, hue wraps around and everything else stops at its limits.
+    pub fn step(self, mut channels: [f32; 3], channel: usize, step: f32) -> [f32; 3] {
+        let value = channels[channel] + step;
+        channels[channel] = match (self, channel) {
+            (Self::Rgb, _) => value.clamp(0.0, 255.0),
+            (_, 0) => value.rem_euclid(360.0),
+            _ => value.clamp(0.0, 100.0),
+        };
+        channels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIMARIES: [RGBA8; 6] = [
+        RGBA8::new(255, 0, 0, 255),
+        RGBA8::new(0, 255, 0, 255),
+        RGBA8::new(0, 0, 255, 255),
+        RGBA8::new(255, 255, 0, 255),
+        RGBA8::new(0, 255, 255, 255),
+        RGBA8::new(255, 0, 255, 255),
+    ];
+
+    fn assert_near(actual: &[f32], expected: &[f32]) {
+        let near = actual.len() == expected.len()
+            && actual
+                .iter()
+                .zip(expected)
+                .all(|(a, b)| (a - b).abs() < 1e-3);
+        assert!(near, "{actual:?} isn't {expected:?}");
+    }
+
+    #[test]
+    fn round_trips() {
+        let greys = [0, 1, 64, 128, 200, 255].map(|level| RGBA8::new(level, level, level, 9));
+        let others = [RGBA8::new(18, 52, 86, 255), RGBA8::new(250, 128, 7, 0)];
+        for mode in [ColorMode::Rgb, ColorMode::Hsv, ColorMode::Hsl] {
+            for color in PRIMARIES.into_iter().chain(greys).chain(others) {
+                let joined = mode.join(mode.split(color), color.a);
+                assert_eq!(joined, color, "{mode:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn hues_of_primaries_and_greys() {
+        let hues = PRIMARIES.map(|color| ColorMode::Hsv.split(color)[0]);
+        assert_near(&hues, &[0.0, 120.0, 240.0, 60.0, 180.0, 300.0]);
+        assert_near(&ColorMode::Hsv.split(PRIMARIES[0]), &[0.0, 100.0, 100.0]);
+        assert_near(&ColorMode::Hsl.split(PRIMARIES[0]), &[0.0, 100.0, 50.0]);
+        let grey = RGBA8::new(128, 128, 128, 255);
+        assert_near(&ColorMode::Hsv.split(grey)[..2], &[0.0, 0.0]);
+        assert_near(&ColorMode::Hsl.split(grey)[..2], &[0.0, 0.0]);
+    }
+
+    #[test]
+    fn hue_wraps_around() {
+        let step = |hue, step| ColorMode::Hsv.step([hue, 50.0, 50.0], 0, step)[0];
+        assert_near(&[step(350.0, 20.0)], &[10.0]);
+        assert_near(&[step(5.0, -10.0)], &[355.0]);
+        assert_near(&[step(359.0, 1.0)], &[0.0]);
+        assert_near(&[step(0.0, -1.0)], &[359.0]);
+        for mode in [ColorMode::Hsv, ColorMode::Hsl] {
+            assert_eq!(
+                mode.join([360.0, 100.0, mode.split(PRIMARIES[0])[2]], 255),
+                PRIMARIES[0]
+            );
+        }
+        // Everything else stops at its limits.
+        assert_near(
+            &ColorMode::Hsl.step([0.0, 95.0, 3.0], 1, 10.0),
+            &[0.0, 100.0, 3.0],
+        );
+        assert_near(
+            &ColorMode::Hsl.step([0.0, 95.0, 3.0], 2, -10.0),
+            &[0.0, 95.0, 0.0],
+        );
+        assert_near(
+            &ColorMode::Rgb.step([250.0, 0.0, 0.0], 0, 10.0),
+            &[255.0, 0.0, 0.0],
+        );
+    }
+}
@@ -1,3 +1,6 @@
+use std::str::FromStr;
+
+#[derive(Clone)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -9,7 +12,7 @@ impl Vec2 {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Size {
     pub width: u32,
     pub height: u32,
@@ -20,17 +23,28 @@ impl Size {
         Self { width, height }
     }
 
-    pub fn area(&self) -> usize {
-        (self.width * self.height) as usize
+    pub fn area(self) -> usize {
+        self.width as usize * self.height as usize
     }
 }
 
-pub fn constrain<T: PartialOrd>(this: T, min: T, max: T) -> T {
-    assert!(min < max);
-    if this < min {
-        return min;
-    } else if this > max {
-        return max;
+/// Parses sizes written as `WIDTHxHEIGHT`.
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .split_once('x')
+            .ok_or_else(|| format!("Expected WIDTHxHEIGHT, got {s}"))?;
+        let width = width
+            .parse::<u32>()
+            .map_err(|_| format!("Couldn't parse width {width}"))?;
+        let height = height
+            .parse::<u32>()
+            .map_err(|_| format!("Couldn't parse height {height}"))?;
+        if width == 0 || height == 0 {
+            return Err(format!("Size {s} is empty"));
+        }
+        Ok(Self::new(width, height))
     }
-    this
 }
@@ -0,0 +1,58 @@
+//! Errors of loading images and projects, shared by every codec.
+
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while loading an image or a project.
+#[derive(Debug)]
+pub enum ImageError {
+    /// The magic number or version isn't one of the supported ones.
+    UnsupportedFormat(String),
+    /// A header field is missing or invalid.
+    BadHeader(String),
+    /// A ppt file ends before all the samples promised by the header.
+    TruncatedData {
+        expected: usize,
+        found: usize,
+    },
+    /// A ppt sample isn't a number in 0..=maxval.
+    SampleOutOfRange {
+        index: usize,
+        sample: String,
+        maxval: u16,
+    },
+    /// Another image codec rejected the file.
+    Decode(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(magic) => write!(f, "Unsupported image format {magic:?}"),
+            Self::BadHeader(reason) => write!(f, "Bad image header: {reason}"),
+            Self::TruncatedData { expected, found } => write!(
+                f,
+                "Truncated image data: expected {expected} samples, found {found}"
+            ),
+            Self::SampleOutOfRange {
+                index,
+                sample,
+                maxval,
+            } => write!(
+                f,
+                "Sample {index} is {sample:?}, expected a number in 0..={maxval}"
+            ),
+            Self::Decode(reason) => write!(f, "Couldn't decode image: {reason}"),
+            Self::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl From<io::Error> for ImageError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
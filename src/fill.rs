@@ -0,0 +1,189 @@
+use simple_pixels::rgb::RGBA8;
+
+use crate::Sprite;
+
+/// Which neighbours a contiguous fill spreads to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FillOptions {
+    pub connectivity: Connectivity,
+    /// Largest per channel difference from the clicked color that still gets filled.
+    pub tolerance: u8,
+    /// Recolor every matching pixel instead of the contiguous area.
+    pub global: bool,
+}
+
+impl Default for FillOptions {
+    fn default() -> Self {
+        Self {
+            connectivity: Connectivity::Four,
+            tolerance: 0,
+            global: false,
+        }
+    }
+}
+
+impl FillOptions {
+    pub fn describe(self) -> String {
+        let mode = if self.global {
+            "global"
+        } else {
+            match self.connectivity {
+                Connectivity::Four => "4-way",
+                Connectivity::Eight => "8-way",
+            }
+        };
+        format!("{mode},tolerance:{}", self.tolerance)
+    }
+
+    fn matches(self, target: RGBA8, pixel: RGBA8) -> bool {
+        let close = |a: u8, b: u8| a.abs_diff(b) <= self.tolerance;
+        close(target.r, pixel.r)
+            && close(target.g, pixel.g)
+            && close(target.b, pixel.b)
+            && close(target.a, pixel.a)
+    }
+}
+
+/// Indices of the pixels a fill started at `start` recolors.
+pub fn fill_region(sprite: &Sprite, start: (i32, i32), options: FillOptions) -> Vec<usize> {
+    let (width, height) = (sprite.size.width as i32, sprite.size.height as i32);
+    let index = |(x, y): (i32, i32)| (y * width + x) as usize;
+    let target = sprite.pixels[index(start)];
+    if options.global {
+        return sprite
+            .pixels
+            .iter()
+            .enumerate()
+            .filter(|(_, &pixel)| options.matches(target, pixel))
+            .map(|(index, _)| index)
+            .collect();
+    }
+
+    let neighbours: &[(i32, i32)] = match options.connectivity {
+        Connectivity::Four => &[(1, 0), (-1, 0), (0, 1), (0, -1)],
+        Connectivity::Eight => &[
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (1, -1),
+            (-1, 1),
+            (-1, -1),
+        ],
+    };
+    let mut visited = vec![false; sprite.pixels.len()];
+    let mut region = Vec::new();
+    let mut stack = vec![start];
+    visited[index(start)] = true;
+    while let Some(cell) = stack.pop() {
+        region.push(index(cell));
+        for (dx, dy) in neighbours {
+            let next = (cell.0 + dx, cell.1 + dy);
+            if next.0 < 0 || next.1 < 0 || next.0 >= width || next.1 >= height {
+                continue;
+            }
+            let next_index = index(next);
+            if !visited[next_index] && options.matches(target, sprite.pixels[next_index]) {
+                visited[next_index] = true;
+                stack.push(next);
+            }
+        }
+    }
+    region
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Size, Vec2};
+
+    /// Rows of `.` white, `,` almost white and `#` black pixels.
+    fn grid(rows: &[&str]) -> Sprite {
+        let pixels = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|ch| match ch {
+                '.' => RGBA8::new(255, 255, 255, 255),
+                ',' => RGBA8::new(250, 255, 252, 255),
+                _ => RGBA8::new(0, 0, 0, 255),
+            })
+            .collect();
+        let size = Size::new(rows[0].len() as u32, rows.len() as u32);
+        Sprite::new(Vec2::new(0.0, 0.0), size, pixels)
+    }
+
+    fn filled(sprite: &Sprite, start: (i32, i32), options: FillOptions) -> Vec<usize> {
+        let mut region = fill_region(sprite, start, options);
+        region.sort_unstable();
+        region
+    }
+
+    const DIAGONAL: [&str; 4] = ["..#.", "..#.", "##..", "...,"];
+
+    #[test]
+    fn four_and_eight_way_fills() {
+        let sprite = grid(&DIAGONAL);
+        let four = FillOptions::default();
+        assert_eq!(filled(&sprite, (0, 0), four), [0, 1, 4, 5]);
+        let eight = FillOptions {
+            connectivity: Connectivity::Eight,
+            ..four
+        };
+        assert_eq!(
+            filled(&sprite, (0, 0), eight),
+            [0, 1, 3, 4, 5, 7, 10, 11, 12, 13, 14]
+        );
+        // Black pixels touch the start only diagonally.
+        assert_eq!(filled(&sprite, (2, 0), four), [2, 6]);
+        assert_eq!(filled(&sprite, (2, 0), eight), [2, 6, 8, 9]);
+    }
+
+    #[test]
+    fn tolerance() {
+        let sprite = grid(&DIAGONAL);
+        let options = FillOptions {
+            connectivity: Connectivity::Eight,
+            tolerance: 4,
+            global: false,
+        };
+        assert!(!filled(&sprite, (0, 0), options).contains(&15));
+        let options = FillOptions {
+            tolerance: 5,
+            ..options
+        };
+        assert!(filled(&sprite, (0, 0), options).contains(&15));
+        // Starting on the almost white pixel compares against it instead.
+        assert_eq!(filled(&sprite, (3, 3), FillOptions::default()), [15]);
+        let options = FillOptions {
+            tolerance: 255,
+            ..FillOptions::default()
+        };
+        assert_eq!(filled(&sprite, (3, 3), options).len(), 16);
+    }
+
+    #[test]
+    fn global_fill_ignores_contiguity() {
+        let sprite = grid(&DIAGONAL);
+        let options = FillOptions {
+            global: true,
+            ..FillOptions::default()
+        };
+        assert_eq!(filled(&sprite, (2, 0), options), [2, 6, 8, 9]);
+        assert_eq!(
+            filled(&sprite, (0, 0), options),
+            [0, 1, 3, 4, 5, 7, 10, 11, 12, 13, 14]
+        );
+        let options = FillOptions {
+            tolerance: 5,
+            ..options
+        };
+        assert_eq!(filled(&sprite, (0, 0), options).len(), 12);
+    }
+}
@@ -1,105 +1,68 @@
-
 use std::fs;
-use std::io;
-use std::mem;
 use std::path::Path;
 
 use simple_pixels::{rgb::RGBA8, Context};
 
 use crate::common::Vec2;
+use crate::error::ImageError;
+use crate::ppt::decode_sprite;
 
 const CHAR_WIDTH: usize = 9;
-const CHAR_HEIGHT: usize = 14;
+pub const CHAR_HEIGHT: usize = 14;
 
 pub struct LetterSprite {
     pub pixels: [RGBA8; CHAR_WIDTH * CHAR_HEIGHT],
 }
 
 impl LetterSprite {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
-        let data = fs::read_to_string(path)?;
-        if let Some((header, data)) = data.split_once('\n') {
-            let data: Vec<&str> = data.split_ascii_whitespace().collect();
-            let mut split = header.split_whitespace();
-            let format = split
-                .next()
-                .expect("Error while parsing ppt sprite: no format!");
-            assert!(format == "P3", "Only support P3 ppt version");
-            let width = split
-                .next()
-                .expect("Error while parsing ppt sprite: no sprite width!")
-                .parse::<usize>()
-                .expect("couldn't parse sprite width");
-            let height = split
-                .next()
-                .expect("Error while parsing ppt sprite: no sprite height!")
-                .parse::<usize>()
-                .expect("couldn't parse sprite height");
-            let colors = split
-                .next()
-                .expect("Error while parsing ppt sprite: no max colors!");
-            assert!(colors == "255", "Not yet support anything but 255 colors");
-            assert!(split.next().is_none(), "Unknown additional header fields");
-
-            assert!(width == CHAR_WIDTH);
-            assert!(height == CHAR_HEIGHT);
-            let mut p: [mem::MaybeUninit<RGBA8>; CHAR_HEIGHT * CHAR_WIDTH] =
-                unsafe { mem::MaybeUninit::uninit().assume_init() };
-            for y in 0..height {
-                for x in 0..width {
-                    let r = data[y * width * 3 + x * 3];
-                    let g = data[y * width * 3 + x * 3 + 1];
-                    let b = data[y * width * 3 + x * 3 + 2];
-                    p[y * width + x] = mem::MaybeUninit::new(RGBA8::new(
-                        r.parse::<u8>().unwrap(),
-                        g.parse::<u8>().unwrap(),
-                        b.parse::<u8>().unwrap(),
-                        0,
-                    ));
-                }
-            }
-            let pixels = unsafe { mem::transmute::<_, [RGBA8; CHAR_HEIGHT * CHAR_WIDTH]>(p) };
-            return Ok(Self { pixels });
-        }
-
-        Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Couldn't split file on newline!",
-        ))
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        let data = fs::read(path)?;
+        let (sprite, _encoding) = decode_sprite(&data)?;
+        let (width, height) = (sprite.size.width, sprite.size.height);
+        let pixels = sprite.pixels.try_into().map_err(|_| {
+            ImageError::BadHeader(format!(
+                "letter sprite is {width}x{height}, expected {CHAR_WIDTH}x{CHAR_HEIGHT}"
+            ))
+        })?;
+        Ok(Self { pixels })
     }
 }
 
+/// Drawn for characters without a letter, names in loaded files can have any.
+const PLACEHOLDER: char = '?';
+
 const ASCII: &str = "!\"#$%&\'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
 pub struct Font {
     letters: Vec<LetterSprite>,
 }
 
 impl Font {
-    pub fn new() -> Self {
-        let mut letters = Vec::with_capacity(ASCII.len());
+    pub fn new() -> Result<Self, ImageError> {
         const FILE_EXTENSION: &str = ".ppt";
         const FONT_FOLDER: &str = "font/";
-        for (_i, letter) in ASCII.chars().enumerate() {
+        let mut letters = Vec::with_capacity(ASCII.len());
+        for letter in ASCII.chars() {
             let file_name: String = if letter == '/' {
                 "slash".to_string()
             } else {
                 letter.to_string()
             };
             let file_path = format!("{FONT_FOLDER}{file_name}{FILE_EXTENSION}");
-            letters.push(LetterSprite::new(file_path).unwrap());
+            letters.push(LetterSprite::new(file_path)?);
         }
-        Self { letters }
+        Ok(Self { letters })
     }
 
     pub fn letter(&self, ch: char) -> &LetterSprite {
+        let ch = if ('!'..='~').contains(&ch) {
+            ch
+        } else {
+            PLACEHOLDER
+        };
         &self.letters[ch as usize - 33]
     }
 
-    pub fn index(&self, ch: char) -> usize {
-        ch as usize - 33
-    }
-
-    pub fn draw(&self, ctx: &mut Context, text: &str, origin: Vec2) {
+    pub fn draw(&self, ctx: &mut Context, text: &str, origin: &Vec2) {
         let max_pos = Vec2::new(ctx.width() as f32, ctx.height() as f32);
         for (y, line) in text.lines().enumerate() {
             for (x, ch) in line.chars().enumerate() {
@@ -112,11 +75,11 @@ impl Font {
                     continue;
                 }
                 let pos = Vec2::new(pos_x, pos_y);
-                self.draw_char(ctx, ch, pos);
+                self.draw_char(ctx, ch, &pos);
             }
         }
     }
-    pub fn draw_char(&self, ctx: &mut Context, ch: char, pos: Vec2) {
+    pub fn draw_char(&self, ctx: &mut Context, ch: char, pos: &Vec2) {
         if ch.is_whitespace() {
             return;
         }
@@ -134,3 +97,28 @@ impl Font {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn characters_without_letters_use_the_placeholder() {
+        let letters = ASCII
+            .chars()
+            .map(|_| LetterSprite {
+                pixels: [RGBA8::default(); CHAR_WIDTH * CHAR_HEIGHT],
+            })
+            .collect();
+        let font = Font { letters };
+        let placeholder = font.letter(PLACEHOLDER);
+        for ch in ['ü', '\u{7}', '\u{7f}', ' ', '字'] {
+            assert!(std::ptr::eq(font.letter(ch), placeholder), "{ch:?}");
+        }
+        assert!(std::ptr::eq(font.letter('~'), font.letters.last().unwrap()));
+        assert!(std::ptr::eq(
+            font.letter('!'),
+            font.letters.first().unwrap()
+        ));
+    }
+}
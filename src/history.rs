@@ -0,0 +1,298 @@
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+
+use simple_pixels::rgb::RGBA8;
+
+/// One pixel changed by a stroke.
+struct Change {
+    layer: usize,
+    frame: usize,
+    index: usize,
+    before: RGBA8,
+    after: RGBA8,
+}
+
+/// Every pixel changed between pressing and releasing a mouse button.
+#[derive(Default)]
+struct Stroke {
+    changes: Vec<Change>,
+    /// Position of each layer, frame and pixel in `changes`, so repainting a pixel keeps its
+    /// first `before`.
+    positions: HashMap<(usize, usize, usize), usize>,
+}
+
+impl Stroke {
+    fn memory(&self) -> usize {
+        self.changes.len()
+            * (mem::size_of::<Change>() + mem::size_of::<((usize, usize, usize), usize)>())
+    }
+}
+
+/// Stroke based undo and redo, only storing the pixels that changed.
+pub struct History {
+    undo: VecDeque<Stroke>,
+    redo: Vec<Stroke>,
+    current: Option<Stroke>,
+    /// Bytes the undo and redo stacks may use before the oldest strokes are forgotten.
+    memory_limit: usize,
+    memory: usize,
+}
+
+impl History {
+    pub fn new(memory_limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            current: None,
+            memory_limit,
+            memory: 0,
+        }
+    }
+
+    /// Records a pixel change, starting a new stroke if there is none.
+    pub fn record(
+        &mut self,
+        (layer, frame): (usize, usize),
+        index: usize,
+        before: RGBA8,
+        after: RGBA8,
+    ) {
+        let stroke = self.current.get_or_insert_with(Stroke::default);
+        if let Some(&position) = stroke.positions.get(&(layer, frame, index)) {
+            stroke.changes[position].after = after;
+        } else if before != after {
+            stroke
+                .positions
+                .insert((layer, frame, index), stroke.changes.len());
+            stroke.changes.push(Change {
+                layer,
+                frame,
+                index,
+                before,
+                after,
+            });
+        }
+    }
+
+    pub fn end_stroke(&mut self) {
+        let Some(stroke) = self.current.take() else {
+            return;
+        };
+        if stroke.changes.is_empty() {
+            return;
+        }
+        for stroke in self.redo.drain(..) {
+            self.memory -= stroke.memory();
+        }
+        self.memory += stroke.memory();
+        self.undo.push_back(stroke);
+        while self.memory > self.memory_limit {
+            let Some(oldest) = self.undo.pop_front() else {
+                break;
+            };
+            self.memory -= oldest.memory();
+        }
+    }
+
+    /// Points strokes at the new positions of moved layers.
+    pub fn remap_layers(&mut self, remap: impl Fn(usize) -> usize) {
+        self.end_stroke();
+        for stroke in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            for change in &mut stroke.changes {
+                change.layer = remap(change.layer);
+            }
+        }
+    }
+
+    /// Points strokes at the new positions of moved frames.
+    pub fn remap_frames(&mut self, remap: impl Fn(usize) -> usize) {
+        self.end_stroke();
+        for stroke in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            for change in &mut stroke.changes {
+                change.frame = remap(change.frame);
+            }
+        }
+    }
+
+    /// Forgets every stroke, for when layers or frames they refer to are gone.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.current = None;
+        self.memory = 0;
+    }
+
+    /// Reverts the last stroke through `set((layer, frame), index, color)`, returns false if there was
+    /// nothing to undo.
+    pub fn undo(&mut self, mut set: impl FnMut((usize, usize), usize, RGBA8)) -> bool {
+        self.end_stroke();
+        let Some(stroke) = self.undo.pop_back() else {
+            return false;
+        };
+        for change in stroke.changes.iter().rev() {
+            set((change.layer, change.frame), change.index, change.before);
+        }
+        self.redo.push(stroke);
+        true
+    }
+
+    /// Reapplies the last undone stroke, returns false if there was nothing to redo.
+    pub fn redo(&mut self, mut set: impl FnMut((usize, usize), usize, RGBA8)) -> bool {
+        self.end_stroke();
+        let Some(stroke) = self.redo.pop() else {
+            return false;
+        };
+        for change in &stroke.changes {
+            set((change.layer, change.frame), change.index, change.after);
+        }
+        self.undo.push_back(stroke);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: RGBA8 = RGBA8::new(255, 0, 0, 255);
+    const BLUE: RGBA8 = RGBA8::new(0, 0, 255, 255);
+
+    /// Pixels of every layer and frame that aren't transparent.
+    type Canvas = HashMap<(usize, usize, usize), RGBA8>;
+
+    fn paint(
+        history: &mut History,
+        canvas: &mut Canvas,
+        cel: (usize, usize),
+        index: usize,
+        color: RGBA8,
+    ) {
+        let before = canvas
+            .get(&(cel.0, cel.1, index))
+            .copied()
+            .unwrap_or_default();
+        history.record(cel, index, before, color);
+        canvas.insert((cel.0, cel.1, index), color);
+    }
+
+    fn undo(history: &mut History, canvas: &mut Canvas) -> bool {
+        history.undo(|(layer, frame), index, color| {
+            canvas.insert((layer, frame, index), color);
+        })
+    }
+
+    fn redo(history: &mut History, canvas: &mut Canvas) -> bool {
+        history.redo(|(layer, frame), index, color| {
+            canvas.insert((layer, frame, index), color);
+        })
+    }
+
+    fn pixel(canvas: &Canvas, layer: usize, frame: usize, index: usize) -> RGBA8 {
+        canvas
+            .get(&(layer, frame, index))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn undo_and_redo_in_order() {
+        let (mut history, mut canvas) = (History::new(usize::MAX), Canvas::new());
+        paint(&mut history, &mut canvas, (0, 0), 1, RED);
+        // Repainting a pixel in the same stroke keeps what it was before the stroke.
+        paint(&mut history, &mut canvas, (0, 0), 1, BLUE);
+        history.end_stroke();
+        paint(&mut history, &mut canvas, (0, 0), 1, RED);
+        paint(&mut history, &mut canvas, (0, 0), 2, RED);
+        history.end_stroke();
+
+        assert!(undo(&mut history, &mut canvas));
+        assert_eq!(
+            (pixel(&canvas, 0, 0, 1), pixel(&canvas, 0, 0, 2)),
+            (BLUE, RGBA8::default())
+        );
+        assert!(undo(&mut history, &mut canvas));
+        assert_eq!(pixel(&canvas, 0, 0, 1), RGBA8::default());
+        assert!(!undo(&mut history, &mut canvas));
+        assert!(redo(&mut history, &mut canvas));
+        assert_eq!(pixel(&canvas, 0, 0, 1), BLUE);
+        assert!(redo(&mut history, &mut canvas));
+        assert_eq!(
+            (pixel(&canvas, 0, 0, 1), pixel(&canvas, 0, 0, 2)),
+            (RED, RED)
+        );
+        assert!(!redo(&mut history, &mut canvas));
+
+        // New strokes forget what was undone, strokes without changes aren't kept.
+        assert!(undo(&mut history, &mut canvas));
+        paint(&mut history, &mut canvas, (0, 0), 3, RED);
+        history.end_stroke();
+        paint(&mut history, &mut canvas, (0, 0), 3, RED);
+        history.end_stroke();
+        assert!(!redo(&mut history, &mut canvas));
+        assert!(undo(&mut history, &mut canvas));
+        assert_eq!(pixel(&canvas, 0, 0, 3), RGBA8::default());
+        assert!(undo(&mut history, &mut canvas));
+        assert!(!undo(&mut history, &mut canvas));
+    }
+
+    #[test]
+    fn oldest_strokes_are_forgotten_over_the_limit() {
+        let stroke = Stroke {
+            changes: vec![Change {
+                layer: 0,
+                frame: 0,
+                index: 0,
+                before: RED,
+                after: BLUE,
+            }],
+            positions: HashMap::new(),
+        };
+        let (mut history, mut canvas) = (History::new(2 * stroke.memory()), Canvas::new());
+        for index in 0..3 {
+            paint(&mut history, &mut canvas, (0, 0), index, RED);
+            history.end_stroke();
+        }
+        assert!(undo(&mut history, &mut canvas));
+        assert!(undo(&mut history, &mut canvas));
+        assert!(!undo(&mut history, &mut canvas));
+        assert_eq!(pixel(&canvas, 0, 0, 0), RED);
+        // Undone strokes still count towards the limit.
+        assert!(redo(&mut history, &mut canvas));
+        assert!(redo(&mut history, &mut canvas));
+        assert_eq!(pixel(&canvas, 0, 0, 2), RED);
+        assert_eq!(history.memory, 2 * stroke.memory());
+        history.clear();
+        assert_eq!(history.memory, 0);
+        assert!(!undo(&mut history, &mut canvas));
+    }
+
+    #[test]
+    fn remap_inserted_layers_and_frames() {
+        let (mut history, mut canvas) = (History::new(usize::MAX), Canvas::new());
+        paint(&mut history, &mut canvas, (0, 0), 0, RED);
+        paint(&mut history, &mut canvas, (1, 1), 0, BLUE);
+        history.end_stroke();
+        // A layer added above layer 0 and a frame added after frame 0, like the editor does.
+        history.remap_layers(|layer| if layer > 0 { layer + 1 } else { layer });
+        history.remap_frames(|frame| if frame > 0 { frame + 1 } else { frame });
+        assert!(undo(&mut history, &mut canvas));
+        assert_eq!(pixel(&canvas, 0, 0, 0), RGBA8::default());
+        assert_eq!(pixel(&canvas, 2, 2, 0), RGBA8::default());
+        assert_eq!(pixel(&canvas, 1, 1, 0), BLUE);
+
+        // Moved layers and frames swap places, undone strokes follow them too.
+        history.remap_layers(|layer| match layer {
+            0 => 2,
+            2 => 0,
+            layer => layer,
+        });
+        history.remap_frames(|frame| match frame {
+            0 => 2,
+            2 => 0,
+            frame => frame,
+        });
+        let mut set = Vec::new();
+        assert!(history.redo(|cel, index, color| set.push((cel, index, color))));
+        assert_eq!(set, [((2, 2), 0, RED), ((0, 0), 0, BLUE)]);
+    }
+}
@@ -0,0 +1,340 @@
+//! Picks the codec for an image from its file extension.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use gif::{DisposalMethod, Repeat};
+use simple_pixels::rgb::RGBA8;
+
+use crate::error::ImageError;
+use crate::layers::FRAME_DURATION;
+use crate::ppt::{load_sprite, save_sprite, PptEncoding, PptFormat};
+use crate::{Size, Sprite, Vec2};
+
+/// Most pixels a GIF screen may have, since a few bytes can declare one of 65535x65535 pixels and
+/// every frame is a copy of the screen.
+const GIF_SCREEN_LIMIT: usize = 4096 * 4096;
+
+/// How an image is stored on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppt(PptEncoding),
+    Png,
+    Gif(GifEncoding),
+}
+
+impl ImageFormat {
+    /// Format for new files.
+    pub fn for_path(path: &Path) -> Self {
+        if has_extension(path, "png") {
+            Self::Png
+        } else if has_extension(path, "gif") {
+            Self::Gif(GifEncoding::default())
+        } else {
+            Self::Ppt(PptEncoding::for_path(path))
+        }
+    }
+
+    /// Whether pixels that aren't fully opaque are saved at all, GIFs keep them as transparent
+    /// or opaque.
+    pub fn keeps_alpha(self) -> bool {
+        match self {
+            Self::Ppt(encoding) => encoding.format == PptFormat::P7,
+            Self::Png | Self::Gif(_) => true,
+        }
+    }
+}
+
+/// Looping and transparency of saved GIFs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GifEncoding {
+    pub repeat: Repeat,
+    /// Opaque pixels of this color are saved transparent too.
+    pub transparent: Option<RGBA8>,
+}
+
+/// New GIFs loop forever.
+impl Default for GifEncoding {
+    fn default() -> Self {
+        Self {
+            repeat: Repeat::Infinite,
+            transparent: None,
+        }
+    }
+}
+
+fn has_extension(path: &Path, expected: &str) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(expected))
+}
+
+/// Frames of the image with their durations in milliseconds, only GIFs have more than one.
+pub fn load_image(path: &PathBuf) -> Result<(Vec<Sprite>, Vec<u16>, ImageFormat), ImageError> {
+    if has_extension(path, "gif") {
+        let (frames, durations, encoding) = load_gif(path)?;
+        return Ok((frames, durations, ImageFormat::Gif(encoding)));
+    }
+    let (sprite, format) = if has_extension(path, "png") {
+        (load_png(path)?, ImageFormat::Png)
+    } else {
+        let (sprite, encoding) = load_sprite(path)?;
+        (sprite, ImageFormat::Ppt(encoding))
+    };
+    Ok((vec![sprite], vec![FRAME_DURATION], format))
+}
+
+pub fn save_image(path: &PathBuf, sprite: &Sprite, format: ImageFormat) -> Result<(), io::Error> {
+    match format {
+        ImageFormat::Ppt(encoding) => save_sprite(path, sprite, encoding),
+        ImageFormat::Png => save_png(path, sprite),
+        ImageFormat::Gif(encoding) => save_gif(
+            path,
+            std::slice::from_ref(sprite),
+            &[FRAME_DURATION],
+            encoding,
+        ),
+    }
+}
+
+fn load_png(path: &Path) -> Result<Sprite, ImageError> {
+    let file = File::open(path)?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    // Palettes, transparency chunks and 16 bit samples all end up as 8 bit gray or rgb.
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|error| ImageError::Decode(error.to_string()))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|error| ImageError::Decode(error.to_string()))?;
+    let buffer = &buffer[..info.buffer_size()];
+
+    let pixels: Vec<RGBA8> = match info.color_type {
+        png::ColorType::Grayscale => buffer
+            .iter()
+            .map(|&value| RGBA8::new(value, value, value, 255))
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .map(|pair| RGBA8::new(pair[0], pair[0], pair[0], pair[1]))
+            .collect(),
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .map(|rgb| RGBA8::new(rgb[0], rgb[1], rgb[2], 255))
+            .collect(),
+        png::ColorType::Rgba => buffer
+            .chunks_exact(4)
+            .map(|rgba| RGBA8::new(rgba[0], rgba[1], rgba[2], rgba[3]))
+            .collect(),
+        png::ColorType::Indexed => {
+            return Err(ImageError::Decode(
+                "indexed png wasn't expanded".to_string(),
+            ))
+        }
+    };
+
+    Ok(Sprite::new(
+        Vec2::new(0.0, 0.0),
+        Size::new(info.width, info.height),
+        pixels,
+    ))
+}
+
+/// Colors of the sprite in order of appearance, `None` if there are more than 256.
+fn palette(sprite: &Sprite) -> Option<(Vec<RGBA8>, Vec<u8>)> {
+    let mut colors: Vec<RGBA8> = Vec::new();
+    let mut lookup: HashMap<RGBA8, u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(sprite.pixels.len());
+    for pixel in &sprite.pixels {
+        let index = if let Some(&index) = lookup.get(pixel) {
+            index
+        } else {
+            let index = u8::try_from(colors.len()).ok()?;
+            colors.push(*pixel);
+            lookup.insert(*pixel, index);
+            index
+        };
+        indices.push(index);
+    }
+    Some((colors, indices))
+}
+
+/// Saves indexed when the sprite has few enough colors, rgba otherwise.
+fn save_png(path: &Path, sprite: &Sprite) -> Result<(), io::Error> {
+    let file = File::create(path)?;
+    let mut encoder =
+        png::Encoder::new(BufWriter::new(file), sprite.size.width, sprite.size.height);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data: Vec<u8> = if let Some((colors, indices)) = palette(sprite) {
+        encoder.set_color(png::ColorType::Indexed);
+        let rgb: Vec<u8> = colors
+            .iter()
+            .flat_map(|color| [color.r, color.g, color.b])
+            .collect();
+        encoder.set_palette(rgb);
+        // Trailing opaque entries can be left out of the transparency chunk.
+        let mut alpha: Vec<u8> = colors.iter().map(|color| color.a).collect();
+        while alpha.last() == Some(&255) {
+            alpha.pop();
+        }
+        if !alpha.is_empty() {
+            encoder.set_trns(alpha);
+        }
+        indices
+    } else {
+        encoder.set_color(png::ColorType::Rgba);
+        sprite
+            .pixels
+            .iter()
+            .flat_map(|pixel| [pixel.r, pixel.g, pixel.b, pixel.a])
+            .collect()
+    };
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&data).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+/// Frames as players show them, composited onto the screen of the GIF and disposed of as each
+/// frame asks, with their durations in milliseconds.
+fn load_gif(path: &Path) -> Result<(Vec<Sprite>, Vec<u16>, GifEncoding), ImageError> {
+    let decode_error = |error: gif::DecodingError| ImageError::Decode(error.to_string());
+    let file = File::open(path)?;
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options
+        .read_info(BufReader::new(file))
+        .map_err(decode_error)?;
+    let size = Size::new(decoder.width().into(), decoder.height().into());
+    if size.area() == 0 || size.area() > GIF_SCREEN_LIMIT {
+        return Err(ImageError::BadHeader(format!(
+            "GIF screen of {}x{} pixels",
+            size.width, size.height
+        )));
+    }
+    let width = size.width as usize;
+    let mut screen = vec![RGBA8::default(); size.area()];
+    let mut frames = Vec::new();
+    let mut durations = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(decode_error)? {
+        let previous = (frame.dispose == DisposalMethod::Previous).then(|| screen.clone());
+        // Frames can be smaller than the screen, and hang over its edges.
+        let (left, top) = (usize::from(frame.left), usize::from(frame.top));
+        let visible = |x: usize, y: usize| x < width && y < size.height as usize;
+        let rows = frame.buffer.chunks_exact(usize::from(frame.width) * 4);
+        for (y, row) in rows.enumerate() {
+            for (x, rgba) in row.chunks_exact(4).enumerate() {
+                if rgba[3] != 0 && visible(left + x, top + y) {
+                    let color = RGBA8::new(rgba[0], rgba[1], rgba[2], rgba[3]);
+                    screen[(top + y) * width + left + x] = color;
+                }
+            }
+        }
+        frames.push(Sprite::new(Vec2::new(0.0, 0.0), size, screen.clone()));
+        // Players show frames without a delay for about as long as new frames last.
+        durations.push(if frame.delay == 0 {
+            FRAME_DURATION
+        } else {
+            frame.delay.saturating_mul(10)
+        });
+        match (frame.dispose, previous) {
+            (DisposalMethod::Background, _) => {
+                for y in top..(top + usize::from(frame.height)).min(size.height as usize) {
+                    let row = y * width;
+                    let right = (left + usize::from(frame.width)).min(width);
+                    if left < right {
+                        screen[row + left..row + right].fill(RGBA8::default());
+                    }
+                }
+            }
+            (DisposalMethod::Previous, Some(previous)) => screen = previous,
+            _ => {}
+        }
+    }
+    if frames.is_empty() {
+        return Err(ImageError::Decode("GIF without frames".to_string()));
+    }
+    let encoding = GifEncoding {
+        repeat: decoder.repeat(),
+        transparent: None,
+    };
+    Ok((frames, durations, encoding))
+}
+
+/// Saves every frame with its duration in milliseconds, rounded to the hundredths of a second
+/// GIFs count in. Frames with more than 256 colors are quantized.
+pub fn save_gif(
+    path: &Path,
+    frames: &[Sprite],
+    durations: &[u16],
+    encoding: GifEncoding,
+) -> Result<(), io::Error> {
+    let size = frames[0].size;
+    let (Ok(width), Ok(height)) = (u16::try_from(size.width), u16::try_from(size.height)) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "GIFs can't be larger than 65535x65535",
+        ));
+    };
+    let file = File::create(path)?;
+    let mut encoder =
+        gif::Encoder::new(BufWriter::new(file), width, height, &[]).map_err(io::Error::other)?;
+    encoder
+        .set_repeat(encoding.repeat)
+        .map_err(io::Error::other)?;
+    // Pixels are either opaque or transparent, the transparent entry of the palette gets the
+    // transparent color for players that ignore transparency.
+    let key = encoding.transparent.unwrap_or_default();
+    let transparent = |pixel: RGBA8| {
+        pixel.a < 128
+            || encoding
+                .transparent
+                .is_some_and(|key| (key.r, key.g, key.b) == (pixel.r, pixel.g, pixel.b))
+    };
+    for (sprite, &duration) in frames.iter().zip(durations) {
+        let mut rgba: Vec<u8> = sprite
+            .pixels
+            .iter()
+            .flat_map(|&pixel| {
+                if transparent(pixel) {
+                    [key.r, key.g, key.b, 0]
+                } else {
+                    [pixel.r, pixel.g, pixel.b, 255]
+                }
+            })
+            .collect();
+        let mut frame = gif::Frame::from_rgba_speed(width, height, &mut rgba, 10);
+        frame.delay = duration.div_ceil(10);
+        // Every frame covers the whole image, its transparent pixels mustn't show the last one.
+        frame.dispose = DisposalMethod::Background;
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_and_huge_gif_screens_are_rejected() {
+        for (name, width, height) in [("empty", 0u16, 0u16), ("huge", 65535, 65535)] {
+            let path = std::env::temp_dir()
+                .join(format!("pixel_editor_{}_{name}.gif", std::process::id()));
+            let pixel = Sprite::new(Vec2::new(0.0, 0.0), Size::new(1, 1), vec![RGBA8::default()]);
+            save_gif(&path, &[pixel], &[100], GifEncoding::default()).unwrap();
+            // Only the logical screen size after the signature changes.
+            let mut data = std::fs::read(&path).unwrap();
+            data[6..8].copy_from_slice(&width.to_le_bytes());
+            data[8..10].copy_from_slice(&height.to_le_bytes());
+            std::fs::write(&path, data).unwrap();
+            let loaded = load_gif(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert!(matches!(loaded, Err(ImageError::BadHeader(_))), "{name}");
+        }
+    }
+}
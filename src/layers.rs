@@ -0,0 +1,371 @@
+//! Stack of layers blended into the image, with a cel of every layer for each animation frame.
+
+use std::str::FromStr;
+
+use simple_pixels::rgb::RGBA8;
+
+use crate::color::{self, BlendMode};
+use crate::sprite::Anchor;
+use crate::{Size, Sprite};
+
+/// Milliseconds frames are shown for unless a file says otherwise.
+pub const FRAME_DURATION: u16 = 100;
+
+#[derive(Clone)]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub opacity: u8,
+    pub blend: BlendMode,
+    /// Pixels of the layer in every frame.
+    pub cels: Vec<Sprite>,
+}
+
+impl Layer {
+    pub fn new(name: String, cels: Vec<Sprite>) -> Self {
+        Self {
+            name,
+            visible: true,
+            opacity: 255,
+            blend: BlendMode::default(),
+            cels,
+        }
+    }
+}
+
+/// Copy of `sprite` with every pixel transparent.
+fn cleared(sprite: &Sprite) -> Sprite {
+    let mut sprite = sprite.clone();
+    sprite.pixels.fill(RGBA8::default());
+    sprite
+}
+
+/// Named run of frames, like one animation of a sprite sheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    /// First and last frame, counted from 0.
+    pub from: usize,
+    pub to: usize,
+}
+
+/// Parses `NAME:FROM-TO` or `NAME:FRAME` with frames counted from 1, as the status line shows
+/// them.
+impl FromStr for Tag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, frames) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected NAME:FROM-TO, got {s}"))?;
+        let (from, to) = frames.split_once('-').unwrap_or((frames, frames));
+        let frame = |frame: &str| {
+            frame
+                .parse::<usize>()
+                .ok()
+                .filter(|&frame| frame > 0)
+                .ok_or_else(|| format!("Couldn't parse frame {frame} of tag {name}"))
+        };
+        let (from, to) = (frame(from)?, frame(to)?);
+        if name.is_empty() || from > to {
+            return Err(format!("Tag {s} is empty"));
+        }
+        // The status line font only has letters for printable ASCII.
+        if !name.chars().all(|ch| ch == ' ' || ch.is_ascii_graphic()) {
+            return Err(format!("Tag name {name} can only use printable ASCII"));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            from: from - 1,
+            to: to - 1,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct LayerStack {
+    /// Bottom layer first.
+    pub layers: Vec<Layer>,
+    /// Layer that is painted on.
+    pub active: usize,
+    /// Milliseconds every frame is shown for during playback, one entry per frame.
+    pub durations: Vec<u16>,
+    /// Frame that is painted on.
+    pub frame: usize,
+    /// Follow frames as they are added and deleted, but not as they are moved.
+    pub tags: Vec<Tag>,
+    /// Numbers the names of new layers.
+    added: usize,
+}
+
+impl LayerStack {
+    /// Stack of a background layer with a cel for every duration.
+    pub fn new(background: Vec<Sprite>, durations: Vec<u16>) -> Self {
+        Self {
+            layers: vec![Layer::new("background".to_string(), background)],
+            active: 0,
+            durations,
+            frame: 0,
+            tags: Vec::new(),
+            added: 0,
+        }
+    }
+
+    /// Stack of loaded layers, which can't be empty and need a cel for every duration.
+    pub fn from_layers(
+        layers: Vec<Layer>,
+        durations: Vec<u16>,
+        active: usize,
+        frame: usize,
+    ) -> Self {
+        Self {
+            active: active.min(layers.len() - 1),
+            frame: frame.min(durations.len() - 1),
+            tags: Vec::new(),
+            added: layers.len() - 1,
+            layers,
+            durations,
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        self.layers[0].cels[0].size
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.durations.len()
+    }
+
+    /// Crops or pads every cel.
+    pub fn resize(&mut self, size: Size, anchor: Anchor) {
+        for cel in self.layers.iter_mut().flat_map(|layer| &mut layer.cels) {
+            *cel = cel.resized(size, anchor);
+        }
+    }
+
+    pub fn active(&self) -> &Layer {
+        &self.layers[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Layer {
+        &mut self.layers[self.active]
+    }
+
+    /// Cel of the active layer in the active frame.
+    pub fn canvas(&self) -> &Sprite {
+        &self.active().cels[self.frame]
+    }
+
+    pub fn canvas_mut(&mut self) -> &mut Sprite {
+        let frame = self.frame;
+        &mut self.active_mut().cels[frame]
+    }
+
+    /// Selects the layer above, or below when `up` is false, stopping at the ends.
+    pub fn select(&mut self, up: bool) {
+        self.active = if up {
+            (self.active + 1).min(self.layers.len() - 1)
+        } else {
+            self.active.saturating_sub(1)
+        };
+    }
+
+    /// Inserts a layer above the active one and selects it.
+    fn insert(&mut self, layer: Layer) {
+        self.active += 1;
+        self.layers.insert(self.active, layer);
+    }
+
+    /// Adds a transparent layer above the active one.
+    pub fn add(&mut self) {
+        self.added += 1;
+        let cels = self.active().cels.iter().map(cleared).collect();
+        self.insert(Layer::new(format!("layer {}", self.added), cels));
+    }
+
+    pub fn duplicate(&mut self) {
+        let active = self.active();
+        let layer = Layer {
+            name: format!("{} copy", active.name),
+            cels: active.cels.clone(),
+            ..*active
+        };
+        self.insert(layer);
+    }
+
+    /// Deletes the active layer, returns false if it's the only one.
+    pub fn delete(&mut self) -> bool {
+        if self.layers.len() == 1 {
+            return false;
+        }
+        self.layers.remove(self.active);
+        self.active = self.active.min(self.layers.len() - 1);
+        true
+    }
+
+    /// Swaps the active layer with the one above, or below when `up` is false, returns false
+    /// at the ends of the stack.
+    pub fn move_active(&mut self, up: bool) -> bool {
+        let other = if up {
+            self.active + 1
+        } else if let Some(below) = self.active.checked_sub(1) {
+            below
+        } else {
+            return false;
+        };
+        if other >= self.layers.len() {
+            return false;
+        }
+        self.layers.swap(self.active, other);
+        self.active = other;
+        true
+    }
+
+    /// Blends the active layer into the one below, keeping the settings of the one below.
+    /// Returns false for the bottom layer and hidden layers, which would be lost.
+    pub fn merge_down(&mut self) -> bool {
+        if self.active == 0 || !self.active().visible {
+            return false;
+        }
+        let top = self.layers.remove(self.active);
+        self.active -= 1;
+        let bottom = &mut self.active_mut().cels;
+        for (bottom, top_cel) in bottom.iter_mut().zip(&top.cels) {
+            for (below, &pixel) in bottom.pixels.iter_mut().zip(&top_cel.pixels) {
+                *below = color::blend(pixel, *below, top.blend, top.opacity);
+            }
+        }
+        true
+    }
+
+    /// Selects the next frame, or the previous one when `forward` is false, wrapping around.
+    pub fn select_frame(&mut self, forward: bool) {
+        let count = self.frame_count();
+        self.frame = if forward {
+            (self.frame + 1) % count
+        } else {
+            (self.frame + count - 1) % count
+        };
+    }
+
+    /// Inserts a frame after the active one, with its duration, and selects it. Tags the
+    /// active frame is in grow to include it.
+    fn insert_frame(&mut self, cel: impl Fn(&Sprite) -> Sprite) {
+        let frame = self.frame;
+        for layer in &mut self.layers {
+            let inserted = cel(&layer.cels[frame]);
+            layer.cels.insert(frame + 1, inserted);
+        }
+        self.durations.insert(frame + 1, self.durations[frame]);
+        for tag in &mut self.tags {
+            if tag.from > frame {
+                tag.from += 1;
+            }
+            if tag.to >= frame {
+                tag.to += 1;
+            }
+        }
+        self.frame += 1;
+    }
+
+    /// Adds a transparent frame after the active one.
+    pub fn add_frame(&mut self) {
+        self.insert_frame(cleared);
+    }
+
+    pub fn duplicate_frame(&mut self) {
+        self.insert_frame(Sprite::clone);
+    }
+
+    /// Deletes the active frame, returns false if it's the only one.
+    pub fn delete_frame(&mut self) -> bool {
+        if self.frame_count() == 1 {
+            return false;
+        }
+        for layer in &mut self.layers {
+            layer.cels.remove(self.frame);
+        }
+        self.durations.remove(self.frame);
+        let frame = self.frame;
+        self.tags.retain(|tag| tag.from != frame || tag.to != frame);
+        for tag in &mut self.tags {
+            if tag.from > frame {
+                tag.from -= 1;
+            }
+            if tag.to >= frame {
+                tag.to -= 1;
+            }
+        }
+        self.frame = self.frame.min(self.frame_count() - 1);
+        true
+    }
+
+    /// Swaps the active frame with the next one, or the previous one when `forward` is false,
+    /// returns false at the ends of the timeline.
+    pub fn move_frame(&mut self, forward: bool) -> bool {
+        let other = if forward {
+            self.frame + 1
+        } else if let Some(previous) = self.frame.checked_sub(1) {
+            previous
+        } else {
+            return false;
+        };
+        if other >= self.frame_count() {
+            return false;
+        }
+        for layer in &mut self.layers {
+            layer.cels.swap(self.frame, other);
+        }
+        self.durations.swap(self.frame, other);
+        self.frame = other;
+        true
+    }
+
+    /// One pixel of a frame with the visible layers blended bottom to top.
+    pub fn pixel(&self, frame: usize, index: usize) -> RGBA8 {
+        self.layers
+            .iter()
+            .filter(|layer| layer.visible)
+            .fold(RGBA8::default(), |below, layer| {
+                color::blend(
+                    layer.cels[frame].pixels[index],
+                    below,
+                    layer.blend,
+                    layer.opacity,
+                )
+            })
+    }
+
+    /// The visible layers of a frame blended into one sprite, for drawing and for saving as
+    /// one image.
+    pub fn flatten(&self, frame: usize) -> Sprite {
+        let mut sprite = self.layers[0].cels[frame].clone();
+        for (index, pixel) in sprite.pixels.iter_mut().enumerate() {
+            *pixel = self.pixel(frame, index);
+        }
+        sprite
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tags() {
+        let tag: Tag = "walk cycle:2-4".parse().unwrap();
+        assert_eq!((tag.name.as_str(), tag.from, tag.to), ("walk cycle", 1, 3));
+        let tag: Tag = "idle:1".parse().unwrap();
+        assert_eq!((tag.from, tag.to), (0, 0));
+        for bad in [
+            "walk",
+            ":1-2",
+            "walk:0-2",
+            "walk:3-2",
+            "gehn\u{e4}:1",
+            "a\tb:1",
+        ] {
+            assert!(bad.parse::<Tag>().is_err(), "{bad}");
+        }
+    }
+}
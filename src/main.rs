@@ -7,243 +7,1429 @@
     clippy::cast_possible_wrap,
     clippy::missing_panics_doc
 )]
-use simple_pixels::{rgb::RGBA8, start, Config, Context, KeyCode, MouseButton, State};
-
+use simple_pixels::{rgb::RGBA8, start, Config, Context, KeyCode, KeyMods, MouseButton, State};
 
+use std::io;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 mod cli;
 mod clock;
+mod color;
 mod common;
+mod error;
+mod fill;
 mod font;
+mod history;
+mod image;
+mod layers;
+mod palette;
 mod ppt;
+mod pxe;
+mod shapes;
+mod sheet;
 mod sprite;
+mod tools;
+mod viewport;
 
 use cli::Arguments;
 use clock::Clock;
-use common::{constrain, Size, Vec2};
-use font::Font;
-use ppt::{load_sprite, save_sprite};
-use sprite::Sprite;
+use color::{BlendMode, ColorMode, CHECKER_DARK, CHECKER_LIGHT};
+use common::{Size, Vec2};
+use error::ImageError;
+use fill::{fill_region, Connectivity, FillOptions};
+use font::{Font, CHAR_HEIGHT};
+use history::History;
+use image::{load_image, save_gif, save_image, GifEncoding, ImageFormat};
+use layers::{LayerStack, Tag, FRAME_DURATION};
+use palette::{Palette, PANEL_WIDTH, SWATCH_SIZE};
+use ppt::{PptEncoding, PptFormat};
+use pxe::{is_project, Project};
+use sheet::{export_sheet, slice_sheet, SheetLayout, SheetOptions, SliceOptions};
+use sprite::{Anchor, Sprite};
+use tools::Tool;
+use viewport::Viewport;
 
 const WIDTH: u32 = 40;
 const HEIGHT: u32 = 30;
+/// KiB of undo history.
+const UNDO_LIMIT: usize = 16 * 1024;
+/// Keys that type into the color entry.
+const TYPED_KEYS: [(KeyCode, char); 28] = [
+    (KeyCode::Key0, '0'),
+    (KeyCode::Key1, '1'),
+    (KeyCode::Key2, '2'),
+    (KeyCode::Key3, '3'),
+    (KeyCode::Key4, '4'),
+    (KeyCode::Key5, '5'),
+    (KeyCode::Key6, '6'),
+    (KeyCode::Key7, '7'),
+    (KeyCode::Key8, '8'),
+    (KeyCode::Key9, '9'),
+    (KeyCode::Kp0, '0'),
+    (KeyCode::Kp1, '1'),
+    (KeyCode::Kp2, '2'),
+    (KeyCode::Kp3, '3'),
+    (KeyCode::Kp4, '4'),
+    (KeyCode::Kp5, '5'),
+    (KeyCode::Kp6, '6'),
+    (KeyCode::Kp7, '7'),
+    (KeyCode::Kp8, '8'),
+    (KeyCode::Kp9, '9'),
+    (KeyCode::A, 'a'),
+    (KeyCode::B, 'b'),
+    (KeyCode::C, 'c'),
+    (KeyCode::D, 'd'),
+    (KeyCode::E, 'e'),
+    (KeyCode::F, 'f'),
+    (KeyCode::Space, ' '),
+    (KeyCode::Comma, ','),
+];
+/// Largest and smallest part of the window showing the canvas, unless given with --window.
+const MAX_VIEW: (u32, u32) = (1024, 768);
+const MIN_VIEW: (u32, u32) = (320, 160);
+/// The pixel grid is only drawn from this zoom up, below it the lines would hide the pixels.
+const GRID_MIN_ZOOM: u32 = 4;
+const GRID_COLOR: RGBA8 = RGBA8::new(80, 80, 80, 255);
+const GUIDE_COLOR: RGBA8 = RGBA8::new(0, 170, 255, 255);
+/// Guide spacing when --guide isn't given.
+const GUIDE: u32 = 8;
+const LAYER_OPACITY_STEP: u8 = 16;
+/// Milliseconds the duration keys add or remove, also the shortest duration.
+const FRAME_DURATION_STEP: u16 = 10;
+/// Tints of the frames before and after the current one, unless given with --onion-past and
+/// --onion-future.
+const ONION_PAST: RGBA8 = RGBA8::new(255, 0, 0, 255);
+const ONION_FUTURE: RGBA8 = RGBA8::new(0, 0, 255, 255);
+/// Opacity of the nearest onion skin frames, halved for every frame further away.
+const ONION_OPACITY: u8 = 128;
+const PANEL_BACKGROUND: RGBA8 = RGBA8::new(40, 40, 40, 255);
+/// The preview pane fits the canvas at 3x, but is never wider than this.
+const PREVIEW_MAX_WIDTH: u32 = 256;
+const PREVIEW_MARGIN: u32 = 4;
+/// Select the first ten palette entries.
+const NUMBER_KEYS: [KeyCode; 10] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0,
+];
 
 fn main() {
     let args = Arguments::new();
 
     assert!(args.unnamed.len() == 1, "Expecting a file path to edit.");
-    let scale = args
-        .named
-        .get("scale")
-        .map(|arg| {
+    let options = Options::new(&args);
+    let file_path = Path::new(args.unnamed.first().unwrap()).to_owned();
+
+    let font = Font::new().unwrap_or_else(|error| {
+        eprintln!("Couldn't load the font: {error}");
+        std::process::exit(1);
+    });
+    let palette = options
+        .palette
+        .as_deref()
+        .map_or_else(Palette::default, load_palette);
+    let game = Game::new(file_path.clone(), options, font, palette).unwrap_or_else(|error| {
+        eprintln!("Couldn't open {}: {error}", file_path.display());
+        std::process::exit(1);
+    });
+    let config = Config {
+        window_title: "game".to_string(),
+        window_width: game.viewport.area.width + PANEL_WIDTH + game.preview_width,
+        window_height: game.viewport.area.height,
+        fullscreen: false,
+        icon: None,
+    };
+    start(config, game);
+}
+
+/// A palette file that doesn't exist yet is created on exit.
+fn load_palette(path: &Path) -> Palette {
+    match Palette::load(path) {
+        Ok(palette) => palette,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Palette {
+            changed: true,
+            ..Palette::default()
+        },
+        Err(error) => {
+            eprintln!("Couldn't load the palette {}: {error}", path.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Loads a project or image, only a missing file starts a new canvas of `size`, anything else
+/// would be overwritten on save.
+fn load_project(
+    path: &PathBuf,
+    size: Option<Size>,
+    import: Option<&(PathBuf, SliceOptions)>,
+) -> Result<(Project, ImageFormat), ImageError> {
+    if let Some((sheet, options)) = import {
+        return import_sheet(path, sheet, *options);
+    }
+    let loaded = if is_project(path) {
+        Project::load(path).map(|project| (project, ImageFormat::for_path(path)))
+    } else {
+        load_image(path)
+            .map(|(frames, durations, format)| (Project::from_frames(frames, durations), format))
+    };
+    match loaded {
+        Err(ImageError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+            let size = size.unwrap_or_else(|| Size::new(WIDTH, HEIGHT));
+            let pixels: Vec<RGBA8> = vec![RGBA8::default(); size.area()];
+
+            Ok((
+                Project::new(Sprite::new(Vec2::new(0.0, 0.0), size, pixels)),
+                ImageFormat::for_path(path),
+            ))
+        }
+        loaded => loaded,
+    }
+}
+
+/// Slices a sheet into the frames of a new file at `path`, existing files aren't replaced.
+fn import_sheet(
+    path: &Path,
+    sheet: &PathBuf,
+    options: SliceOptions,
+) -> Result<(Project, ImageFormat), ImageError> {
+    if path.exists() {
+        return Err(ImageError::Io(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "it already exists, sheets are imported into new files",
+        )));
+    }
+    // Errors would otherwise name the new file.
+    let (mut images, _, _) = load_image(sheet)
+        .map_err(|error| ImageError::Decode(format!("{}: {error}", sheet.display())))?;
+    let frames = slice_sheet(&images.swap_remove(0), options);
+    if frames.is_empty() {
+        let cell = options.cell;
+        return Err(ImageError::Decode(format!(
+            "{} has no {}x{} cells to import",
+            sheet.display(),
+            cell.width,
+            cell.height
+        )));
+    }
+    let durations = vec![FRAME_DURATION; frames.len()];
+    Ok((
+        Project::from_frames(frames, durations),
+        ImageFormat::for_path(path),
+    ))
+}
+
+/// Replaces the tags of the file, leaving out those past the last frame.
+fn apply_tags(layers: &mut LayerStack, mut tags: Vec<Tag>) {
+    let frames = layers.frame_count();
+    tags.retain(|tag| {
+        let inside = tag.to < frames;
+        if !inside {
+            println!("Ignoring tag {}, there are only {frames} frames", tag.name);
+        }
+        inside
+    });
+    layers.tags = tags;
+}
+
+/// Editor settings from the command line.
+struct Options {
+    scale: u32,
+    /// Size of new files.
+    size: Option<Size>,
+    resize: Option<(Size, Anchor)>,
+    ppt_format: Option<PptFormat>,
+    maxval: Option<u16>,
+    /// Bytes of undo history to keep.
+    undo_limit: usize,
+    /// Palette file loaded on start and saved on exit.
+    palette: Option<PathBuf>,
+    /// Size of the canvas view, defaults to the canvas at the starting scale.
+    window: Option<Size>,
+    /// Spacing of the guide grid, which starts shown when given.
+    guide: Option<Size>,
+    /// Frames shown before and after the current one by the onion skin.
+    onion: usize,
+    /// Tints of the frames before and after the current one.
+    onion_tints: (RGBA8, RGBA8),
+    /// How often saved GIFs repeat, defaults to forever for new files.
+    repeat: Option<gif::Repeat>,
+    /// Color saved transparent in GIFs.
+    transparent: Option<RGBA8>,
+    /// Replace the tags of the loaded file.
+    tags: Option<Vec<Tag>>,
+    /// Sprite sheet written along with the file on save.
+    sheet: Option<(PathBuf, SheetOptions)>,
+    /// Sprite sheet sliced into the frames of a new file.
+    import: Option<(PathBuf, SliceOptions)>,
+}
+
+impl Options {
+    fn new(args: &Arguments) -> Self {
+        let scale = args.named.get("scale").map_or(10, |arg| {
             arg.parse::<u32>()
                 .expect("Couldn't parse scale as an integer!")
-        })
-        .unwrap_or_else(|| 10);
-    let width = args
-        .named
-        .get("width")
-        .map(|arg| {
+        });
+        let width = args.named.get("width").map(|arg| {
             arg.parse::<u32>()
                 .expect("Couldn't parse width as an integer!")
-        })
-        .unwrap_or_else(|| WIDTH);
-    let height = args
-        .named
-        .get("height")
-        .map(|arg| {
+        });
+        let height = args.named.get("height").map(|arg| {
             arg.parse::<u32>()
                 .expect("Couldn't parse height as an integer!")
+        });
+        let size = (width.is_some() || height.is_some())
+            .then(|| Size::new(width.unwrap_or(WIDTH), height.unwrap_or(HEIGHT)));
+        let anchor = args
+            .named
+            .get("anchor")
+            .map_or_else(Anchor::default, |arg| {
+                arg.parse::<Anchor>()
+                    .expect("Couldn't parse anchor, expected e.g. top-left, center or bottom!")
+            });
+        let resize = args.named.get("resize").map(|arg| {
+            let size = arg
+                .parse::<Size>()
+                .expect("Couldn't parse resize, expected WIDTHxHEIGHT!");
+            (size, anchor)
+        });
+        let ppt_format = args.named.get("format").map(|arg| {
+            arg.parse::<PptFormat>()
+                .expect("Couldn't parse format, expected one of P1 to P7!")
+        });
+        let maxval = args.named.get("maxval").map(|arg| {
+            arg.parse::<u16>()
+                .ok()
+                .filter(|&maxval| maxval > 0)
+                .expect("Couldn't parse maxval, maxval must be 1..=65535!")
+        });
+        let undo_limit = args.named.get("undo-limit").map_or(UNDO_LIMIT, |arg| {
+            arg.parse::<usize>()
+                .expect("Couldn't parse undo-limit as an integer!")
+        }) * 1024;
+        let palette = args.named.get("palette").map(PathBuf::from);
+        let window = args.named.get("window").map(|arg| {
+            arg.parse::<Size>()
+                .expect("Couldn't parse window, expected WIDTHxHEIGHT!")
+        });
+        let guide = args.named.get("guide").map(|arg| {
+            arg.parse::<u32>()
+                .ok()
+                .filter(|&spacing| spacing > 0)
+                .map_or_else(
+                    || arg.parse::<Size>(),
+                    |spacing| Ok(Size::new(spacing, spacing)),
+                )
+                .expect("Couldn't parse guide, expected SPACING or WIDTHxHEIGHT!")
+        });
+        let onion = args.named.get("onion").map_or(1, |arg| {
+            arg.parse::<usize>()
+                .expect("Couldn't parse onion as an integer!")
+        });
+        let tint = |name: &str, default: RGBA8| {
+            args.named.get(name).map_or(default, |arg| {
+                color::from_hex(arg)
+                    .unwrap_or_else(|| panic!("Couldn't parse {name}, expected RRGGBB!"))
+            })
+        };
+        let onion_tints = (
+            tint("onion-past", ONION_PAST),
+            tint("onion-future", ONION_FUTURE),
+        );
+        let (repeat, transparent) = Self::gif(args);
+        let tags = args.named.get("tags").map(|arg| {
+            arg.split(',')
+                .map(str::parse::<Tag>)
+                .collect::<Result<Vec<_>, _>>()
+                .expect("Couldn't parse tags, expected NAME:FROM-TO separated by commas!")
+        });
+        Self {
+            scale,
+            size,
+            resize,
+            ppt_format,
+            maxval,
+            undo_limit,
+            palette,
+            window,
+            guide,
+            onion,
+            onion_tints,
+            repeat,
+            transparent,
+            tags,
+            sheet: Self::sheet(args),
+            import: Self::import(args),
+        }
+    }
+
+    fn number(args: &Arguments, name: &str) -> Option<u32> {
+        args.named.get(name).map(|arg| {
+            arg.parse::<u32>()
+                .unwrap_or_else(|_| panic!("Couldn't parse {name} as an integer!"))
         })
-        .unwrap_or_else(|| HEIGHT);
-    let file_path = Path::new(args.unnamed.first().unwrap()).to_owned();
-    let config = Config {
-        window_title: "game".to_string(),
-        window_width: width * scale,
-        window_height: height * scale,
-        fullscreen: false,
-        icon: None,
-    };
+    }
 
-    let game = Game::new(file_path, scale, Size::new(width, height));
-    start(config, game);
+    /// Repeats and transparent color of saved GIFs.
+    fn gif(args: &Arguments) -> (Option<gif::Repeat>, Option<RGBA8>) {
+        let repeat = args.named.get("loop").map(|arg| {
+            if arg == "forever" {
+                gif::Repeat::Infinite
+            } else {
+                gif::Repeat::Finite(
+                    arg.parse::<u16>()
+                        .expect("Couldn't parse loop, expected forever or an integer!"),
+                )
+            }
+        });
+        let transparent = args
+            .named
+            .get("transparent")
+            .map(|arg| color::from_hex(arg).expect("Couldn't parse transparent, expected RRGGBB!"));
+        (repeat, transparent)
+    }
+
+    fn sheet(args: &Arguments) -> Option<(PathBuf, SheetOptions)> {
+        let path = PathBuf::from(args.named.get("sheet")?);
+        let number = |name| Self::number(args, name);
+        let layout = args
+            .named
+            .get("sheet-layout")
+            .map_or_else(SheetLayout::default, |arg| {
+                arg.parse::<SheetLayout>()
+                    .expect("Couldn't parse sheet-layout, expected grid or packed!")
+            });
+        let options = SheetOptions {
+            layout,
+            columns: number("columns").filter(|&columns| columns > 0),
+            padding: number("padding").unwrap_or(0),
+            extrude: number("extrude").unwrap_or(0),
+        };
+        Some((path, options))
+    }
+
+    fn import(args: &Arguments) -> Option<(PathBuf, SliceOptions)> {
+        let sheet = PathBuf::from(args.named.get("import-sheet")?);
+        let cell = args
+            .named
+            .get("cell")
+            .expect("Importing a sheet needs a cell size, e.g. --cell 16x16!")
+            .parse::<Size>()
+            .expect("Couldn't parse cell, expected WIDTHxHEIGHT!");
+        let skip_empty = match args.named.get("empty-cells").map(String::as_str) {
+            None | Some("keep") => false,
+            Some("skip") => true,
+            Some(_) => panic!("Couldn't parse empty-cells, expected keep or skip!"),
+        };
+        let options = SliceOptions {
+            cell,
+            margin: Self::number(args, "margin").unwrap_or(0),
+            spacing: Self::number(args, "spacing").unwrap_or(0),
+            skip_empty,
+        };
+        Some((sheet, options))
+    }
 }
 
+/// Channel edited by the step keys, the first three are hue, saturation and value or lightness
+/// outside of the rgb mode.
 enum Selection {
     R,
     G,
     B,
+    A,
+}
+
+/// Frames around the current one drawn tinted over it.
+struct OnionSkin {
+    shown: bool,
+    /// Frames shown before and after the current one.
+    frames: usize,
+    /// Tints of the frames before and after the current one.
+    tints: (RGBA8, RGBA8),
 }
 
 struct Game {
     clock: Clock,
-    canvas: Sprite,
-    scale: u32,
+    layers: LayerStack,
+    viewport: Viewport,
+    /// Mouse position during the last frame of a pan and whether it moved since the press.
+    pan: Option<((i32, i32), bool)>,
     size: Size,
     path: PathBuf,
+    format: ImageFormat,
     font: Font,
     selected_color: RGBA8,
     selection: Selection,
+    color_mode: ColorMode,
+    /// Channels of the color mode for the selected color, kept so that hue survives stepping
+    /// through grays.
+    mode_channels: Option<(RGBA8, [f32; 3])>,
+    /// Hex code or r g b values typed after pressing enter.
+    color_entry: Option<String>,
+    history: History,
+    /// Cell under the cursor during the last frame of a stroke.
+    last_cell: Option<(i32, i32)>,
+    tool: Tool,
+    /// Where the shape being dragged started and its color.
+    drag: Option<((i32, i32), RGBA8)>,
+    cursor: (i32, i32),
+    /// Shift snaps lines to 45 degrees and makes squares and circles.
+    constrain: bool,
+    fill_options: FillOptions,
+    show_grid: bool,
+    guide: Size,
+    show_guide: bool,
+    palette: Palette,
+    palette_path: Option<PathBuf>,
+    /// Metadata of a loaded project, written back when saving it.
+    metadata: Vec<(String, String)>,
+    sheet: Option<(PathBuf, SheetOptions)>,
+    /// Width of the pane right of the palette showing the canvas at actual size.
+    preview_width: u32,
+    /// 1x, 2x or 3x.
+    preview_scale: u32,
+    /// Frame the preview shows while playing and for how long it has been shown.
+    playback: Option<(usize, Duration)>,
+    onion: OnionSkin,
 }
 
 impl Game {
-    pub fn new(file_path: PathBuf, scale: u32, size: Size) -> Self {
-        let mut canvas = load_sprite(&file_path).unwrap_or_else(|_| {
-            let pixels: Vec<RGBA8> = vec![RGBA8::default(); size.area()];
-            
-            Sprite::new(Vec2::new(0.0, 0.0), size, pixels)
-        });
-        canvas = if canvas.size.area() != size.area() {
-            let pixels: Vec<RGBA8> = vec![RGBA8::default(); size.area()];
-            
-            Sprite::new(Vec2::new(0.0, 0.0), size, pixels)
+    pub fn new(
+        file_path: PathBuf,
+        options: Options,
+        font: Font,
+        palette: Palette,
+    ) -> Result<Self, ImageError> {
+        let Options {
+            scale,
+            size,
+            resize,
+            ppt_format,
+            maxval,
+            undo_limit,
+            palette: palette_path,
+            window,
+            guide,
+            onion,
+            onion_tints,
+            repeat,
+            transparent,
+            tags,
+            sheet,
+            import,
+        } = options;
+        let (mut project, loaded_format) = load_project(&file_path, size, import.as_ref())?;
+        let loaded_size = project.layers.size();
+        // The size of an existing file wins, changing it has to be asked for explicitly.
+        if let Some(size) = size.filter(|&size| size != loaded_size) {
+            println!(
+                "Ignoring --width/--height {}x{}, the file is {}x{}, use --resize to change it",
+                size.width, size.height, loaded_size.width, loaded_size.height
+            );
+        }
+        if let Some((size, anchor)) = resize {
+            project.layers.resize(size, anchor);
+        }
+        if let Some(tags) = tags {
+            apply_tags(&mut project.layers, tags);
+        }
+        // A palette given on the command line wins over the one saved in the project.
+        let palette = if palette_path.is_none() && !project.palette.is_empty() {
+            Palette {
+                colors: project.palette,
+                changed: false,
+            }
         } else {
-            canvas
+            palette
+        };
+        // Keep the encoding of the loaded file unless asked otherwise.
+        let format = match loaded_format {
+            ImageFormat::Ppt(encoding) => ImageFormat::Ppt(PptEncoding::new(
+                ppt_format.unwrap_or(encoding.format),
+                maxval.unwrap_or(encoding.maxval),
+            )),
+            ImageFormat::Png => ImageFormat::Png,
+            ImageFormat::Gif(encoding) => ImageFormat::Gif(GifEncoding {
+                repeat: repeat.unwrap_or(encoding.repeat),
+                transparent: transparent.or(encoding.transparent),
+            }),
         };
+        let size = project.layers.size();
+        let area = window.unwrap_or_else(|| {
+            Size::new(
+                (size.width * scale).clamp(MIN_VIEW.0, MAX_VIEW.0),
+                (size.height * scale).clamp(MIN_VIEW.1, MAX_VIEW.1),
+            )
+        });
+        let area = Size::new(area.width.max(MIN_VIEW.0), area.height.max(MIN_VIEW.1));
         let clock = Clock::new();
-        let font = Font::new();
-        let selected_color = RGBA8::new(100, 100, 100, 255);
-        Self {
+        Ok(Self {
             clock,
-            canvas,
-            scale,
+            layers: project.layers,
+            viewport: Viewport::new(scale, size, area),
+            pan: None,
             size,
             path: file_path,
+            format,
             font,
-            selected_color,
+            selected_color: project.selected_color,
             selection: Selection::R,
-        }
+            color_mode: ColorMode::default(),
+            mode_channels: None,
+            color_entry: None,
+            history: History::new(undo_limit),
+            last_cell: None,
+            tool: Tool::Pen,
+            drag: None,
+            cursor: (0, 0),
+            constrain: false,
+            fill_options: FillOptions::default(),
+            show_grid: false,
+            guide: guide.unwrap_or_else(|| Size::new(GUIDE, GUIDE)),
+            show_guide: guide.is_some(),
+            palette,
+            palette_path,
+            metadata: project.metadata,
+            sheet,
+            preview_width: (size.width * 3).min(PREVIEW_MAX_WIDTH) + 2 * PREVIEW_MARGIN,
+            preview_scale: 1,
+            playback: None,
+            onion: OnionSkin {
+                shown: false,
+                frames: onion,
+                tints: onion_tints,
+            },
+        })
     }
 }
 
 impl State for Game {
     fn update(&mut self, ctx: &mut Context) {
         let (_r, _g, _b) = (50, 100, 255);
-        if ctx.is_key_down(KeyCode::Escape) {
+        self.advance_playback();
+        if self.color_entry.is_some() {
+            self.update_color_entry(ctx);
+            self.clock.sleep();
+            return;
+        }
+        if ctx.is_key_pressed(KeyCode::Escape) {
             self.save();
             ctx.quit();
         }
 
         let (x, y) = ctx.get_mouse_pos();
-        let (grid_x, grid_y) = (
-            constrain(x as u32, 0, (self.size.width - 1) * self.scale) as u32 / self.scale,
-            constrain(y as u32, 0, (self.size.height - 1) * self.scale) as u32 / self.scale,
-        );
-        let index = (grid_y * self.canvas.size.width + grid_x) as usize;
-        if ctx.is_mouse_button_down(MouseButton::Right) {
-            self.canvas.pixels[index] = RGBA8::default();
+        let mouse = (x as i32, y as i32);
+        if self.update_pan(ctx, mouse) {
+            self.handle_keys(ctx);
+            self.clock.sleep();
+            return;
         }
-        if ctx.is_mouse_button_down(MouseButton::Left) {
-            self.canvas.pixels[index] = self.selected_color;
+        // Clicks beside the canvas go to the palette unless a stroke or shape is in progress.
+        let idle = self.last_cell.is_none() && self.drag.is_none();
+        if idle && mouse.0 >= self.viewport.area.width as i32 {
+            if ctx.is_mouse_button_pressed(MouseButton::Left) {
+                self.select_swatch_at(x as u32, y as u32);
+            }
+            self.handle_keys(ctx);
+            self.clock.sleep();
+            return;
         }
-        if ctx.is_key_pressed(KeyCode::O) {
-            self.selection_decrease();
-        } else if ctx.is_key_pressed(KeyCode::U) {
-            self.selection_increase();
-        } else if ctx.is_key_pressed(KeyCode::Period) {
-            self.color_increase();
-        } else if ctx.is_key_pressed(KeyCode::E) {
-            self.color_decrease();
+        let over_canvas = self.canvas_cell(mouse).is_some();
+        // Strokes keep going along the edge when the cursor leaves the canvas.
+        let (grid_x, grid_y) = self.viewport.screen_to_cell(mouse);
+        let cell = (
+            grid_x.clamp(0, self.size.width as i32 - 1),
+            grid_y.clamp(0, self.size.height as i32 - 1),
+        );
+        self.cursor = cell;
+        let mods = ctx.get_key_mods();
+        self.constrain = mods.shift;
+        let erasing = ctx.is_mouse_button_down(MouseButton::Right);
+        let painting = ctx.is_mouse_button_down(MouseButton::Left);
+        let color = if painting {
+            self.selected_color
+        } else {
+            RGBA8::default()
+        };
+        // Alt+click picks the color under the cursor with any tool.
+        if mods.alt && painting {
+            if over_canvas {
+                self.selected_color = self.pixel_at(cell);
+            }
+        } else if over_canvas || !idle {
+            self.update_tool(ctx, cell, (erasing || painting).then_some(color));
         }
 
+        self.handle_keys(ctx);
+
         self.clock.sleep();
     }
 
     fn draw(&mut self, ctx: &mut Context) {
         ctx.clear();
 
-        for y in 0..self.canvas.size.height {
-            for x in 0..self.canvas.size.width {
-                let index = (y * self.canvas.size.width + x) as usize;
-                let pixel = self.canvas.pixels[index];
-                ctx.draw_rect(
-                    (x * self.scale).try_into().unwrap(),
-                    (y * self.scale).try_into().unwrap(),
-                    self.scale,
-                    self.scale,
-                    pixel,
-                );
+        let frame = self.layers.frame;
+        let image = self.layers.flatten(frame);
+        let skins = self.onion_skins();
+        let (columns, rows) = self.viewport.visible_cells(self.size);
+        for y in rows {
+            for x in columns.clone() {
+                let index = (y * self.size.width + x) as usize;
+                // Onion skins go under the current frame, so they only show through where it
+                // isn't opaque and never hide the cel being edited.
+                let onion = skins
+                    .iter()
+                    .fold(RGBA8::default(), |below, (skin, tint, opacity)| {
+                        let skin = color::tint(skin.pixels[index], *tint);
+                        color::blend(skin, below, BlendMode::Normal, *opacity)
+                    });
+                let pixel = color::blend(image.pixels[index], onion, BlendMode::Normal, 255);
+                self.draw_cell(ctx, (x as i32, y as i32), pixel);
+            }
+        }
+        if let Some(shape) = self.shape_preview() {
+            let color = self.drag.map_or(self.selected_color, |(_, color)| color);
+            for cell in shape {
+                self.draw_cell(ctx, cell, color);
             }
         }
+        if self.show_grid && self.viewport.zoom >= GRID_MIN_ZOOM {
+            self.draw_grid(ctx, Size::new(1, 1), GRID_COLOR);
+        }
+        if self.show_guide {
+            self.draw_grid(ctx, self.guide, GUIDE_COLOR);
+        }
+        self.draw_palette(ctx);
+        // Frames can be deleted during playback.
+        let shown = self
+            .playback
+            .map_or(frame, |(shown, _)| shown.min(self.layers.frame_count() - 1));
+        if shown == frame {
+            self.draw_preview(ctx, &image);
+        } else {
+            self.draw_preview(ctx, &self.layers.flatten(shown));
+        }
         self.display_selected_color(ctx);
     }
 }
 
 impl Game {
+    /// Applies the current tool, `color` is `None` while no button is held.
+    fn update_tool(&mut self, ctx: &Context, cell: (i32, i32), color: Option<RGBA8>) {
+        match self.tool {
+            Tool::Pen => self.update_pen(cell, color),
+            Tool::Fill => {
+                let pressed = ctx.is_mouse_button_pressed(MouseButton::Left)
+                    || ctx.is_mouse_button_pressed(MouseButton::Right);
+                if let (true, Some(color)) = (pressed, color) {
+                    self.fill(cell, color);
+                }
+            }
+            _ => self.update_shape(cell, color),
+        }
+    }
+
+    /// Middle or space and left drags move the canvas, returns true while panning.
+    fn update_pan(&mut self, ctx: &Context, mouse: (i32, i32)) -> bool {
+        let held = ctx.is_mouse_button_down(MouseButton::Middle)
+            || (ctx.is_key_down(KeyCode::Space) && ctx.is_mouse_button_down(MouseButton::Left));
+        let Some((last, moved)) = self.pan else {
+            let idle = self.last_cell.is_none() && self.drag.is_none();
+            if held && idle {
+                self.pan = Some((mouse, false));
+            }
+            return self.pan.is_some();
+        };
+        if !held {
+            self.pan = None;
+            // A middle click without moving picks the color under the cursor instead.
+            if !moved && ctx.is_mouse_button_released(MouseButton::Middle) {
+                if let Some(cell) = self.canvas_cell(mouse) {
+                    self.selected_color = self.pixel_at(cell);
+                }
+            }
+            return true;
+        }
+        let (dx, dy) = (mouse.0 - last.0, mouse.1 - last.1);
+        self.viewport.pan(dx, dy);
+        self.pan = Some((mouse, moved || dx != 0 || dy != 0));
+        true
+    }
+
+    /// Canvas cell under a screen position, `None` outside of the visible canvas.
+    fn canvas_cell(&self, mouse: (i32, i32)) -> Option<(i32, i32)> {
+        let (x, y) = self.viewport.screen_to_cell(mouse);
+        let inside = x >= 0 && y >= 0 && x < self.size.width as i32 && y < self.size.height as i32;
+        (inside && self.viewport.contains(mouse)).then_some((x, y))
+    }
+
+    /// Visible color of a cell with every layer blended.
+    fn pixel_at(&self, (x, y): (i32, i32)) -> RGBA8 {
+        let index = (y as u32 * self.size.width + x as u32) as usize;
+        self.layers.pixel(self.layers.frame, index)
+    }
+
+    /// Paints while a button is held, `color` is `None` once it's released.
+    fn update_pen(&mut self, cell: (i32, i32), color: Option<RGBA8>) {
+        if let Some(color) = color {
+            // Fill the gaps left by fast drags between two frames.
+            let from = self.last_cell.unwrap_or(cell);
+            for (x, y) in shapes::line(from, cell) {
+                self.paint_cell(x, y, color);
+            }
+            self.last_cell = Some(cell);
+        } else {
+            self.last_cell = None;
+            self.history.end_stroke();
+        }
+    }
+
+    /// Starts a shape on press and commits it to the canvas on release.
+    fn update_shape(&mut self, cell: (i32, i32), color: Option<RGBA8>) {
+        if let Some(color) = color {
+            if self.drag.is_none() {
+                self.drag = Some((cell, color));
+            }
+        } else if let Some(shape) = self.shape_preview() {
+            let (_, color) = self.drag.take().expect("Previews need a drag");
+            for (x, y) in shape {
+                self.paint_cell(x, y, color);
+            }
+            self.history.end_stroke();
+        }
+    }
+
+    fn fill(&mut self, cell: (i32, i32), color: RGBA8) {
+        let region = fill_region(self.layers.canvas(), cell, self.fill_options);
+        for index in region {
+            self.paint(index, color);
+        }
+        self.history.end_stroke();
+    }
+
+    /// Cells of the shape being dragged, clipped to the canvas.
+    fn shape_preview(&self) -> Option<Vec<(i32, i32)>> {
+        let (start, _) = self.drag?;
+        let size = self.size;
+        let mut cells = self.tool.shape(start, self.cursor, self.constrain)?;
+        cells.retain(|&(x, y)| x >= 0 && y >= 0 && x < size.width as i32 && y < size.height as i32);
+        Some(cells)
+    }
+
+    /// Zooms around the cursor, or the middle of the view when the cursor is outside of it.
+    fn handle_view_keys(&mut self, ctx: &Context) -> bool {
+        let ctrl = ctx.get_key_mods().ctrl;
+        let (x, y) = ctx.get_mouse_pos();
+        let mut around = (x as i32, y as i32);
+        if !self.viewport.contains(around) {
+            let area = self.viewport.area;
+            around = (area.width as i32 / 2, area.height as i32 / 2);
+        }
+        if (ctrl && ctx.is_key_pressed(KeyCode::Equal)) || ctx.is_key_pressed(KeyCode::KpAdd) {
+            self.viewport.zoom_in(around);
+        } else if (ctrl && ctx.is_key_pressed(KeyCode::Minus))
+            || ctx.is_key_pressed(KeyCode::KpSubtract)
+        {
+            self.viewport.zoom_out(around);
+        } else if ctrl && ctx.is_key_pressed(KeyCode::Key0) {
+            self.viewport.fit(self.size);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Layer keys, structural changes end the current stroke and shape.
+    fn handle_layer_keys(&mut self, ctx: &Context) -> bool {
+        let shift = ctx.get_key_mods().shift;
+        let layers = &mut self.layers;
+        let active = layers.active;
+        if ctx.is_key_pressed(KeyCode::Tab) {
+            layers.select(!shift);
+        } else if ctx.is_key_pressed(KeyCode::N) || ctx.is_key_pressed(KeyCode::D) {
+            if ctx.is_key_pressed(KeyCode::N) {
+                layers.add();
+            } else {
+                layers.duplicate();
+            }
+            let shift_above = |layer| if layer > active { layer + 1 } else { layer };
+            self.history.remap_layers(shift_above);
+        } else if ctx.is_key_pressed(KeyCode::PageUp) || ctx.is_key_pressed(KeyCode::PageDown) {
+            if layers.move_active(ctx.is_key_pressed(KeyCode::PageUp)) {
+                let other = layers.active;
+                let swap = |layer| match layer {
+                    layer if layer == active => other,
+                    layer if layer == other => active,
+                    layer => layer,
+                };
+                self.history.remap_layers(swap);
+            }
+        } else if ctx.is_key_pressed(KeyCode::Delete) {
+            if layers.delete() {
+                self.history.clear();
+            }
+        } else if ctx.is_key_pressed(KeyCode::J) {
+            if layers.merge_down() {
+                self.history.clear();
+            }
+        } else if ctx.is_key_pressed(KeyCode::H) {
+            layers.active_mut().visible = !layers.active().visible;
+        } else if ctx.is_key_pressed(KeyCode::Y) {
+            layers.active_mut().blend = layers.active().blend.next();
+        } else if ctx.is_key_pressed(KeyCode::Semicolon) {
+            let layer = layers.active_mut();
+            layer.opacity = layer.opacity.saturating_sub(LAYER_OPACITY_STEP);
+        } else if ctx.is_key_pressed(KeyCode::Apostrophe) {
+            let layer = layers.active_mut();
+            layer.opacity = layer.opacity.saturating_add(LAYER_OPACITY_STEP);
+        } else {
+            return false;
+        }
+        self.drag = None;
+        self.last_cell = None;
+        self.history.end_stroke();
+        true
+    }
+
+    /// Frame and playback keys, structural changes end the current stroke and shape.
+    fn handle_frame_keys(&mut self, ctx: &Context) -> bool {
+        let shift = ctx.get_key_mods().shift;
+        let layers = &mut self.layers;
+        let frame = layers.frame;
+        let left = ctx.is_key_pressed(KeyCode::Left);
+        let step = left || ctx.is_key_pressed(KeyCode::Right);
+        if step && shift {
+            if layers.move_frame(!left) {
+                let other = layers.frame;
+                let swap = |moved| match moved {
+                    moved if moved == frame => other,
+                    moved if moved == other => frame,
+                    moved => moved,
+                };
+                self.history.remap_frames(swap);
+            }
+        } else if step {
+            layers.select_frame(!left);
+        } else if ctx.is_key_pressed(KeyCode::F) {
+            if shift {
+                layers.duplicate_frame();
+            } else {
+                layers.add_frame();
+            }
+            let shift_after = |moved| if moved > frame { moved + 1 } else { moved };
+            self.history.remap_frames(shift_after);
+        } else if shift && ctx.is_key_pressed(KeyCode::Delete) {
+            if layers.delete_frame() {
+                self.history.clear();
+            }
+        } else if ctx.is_key_pressed(KeyCode::Up) {
+            let duration = &mut layers.durations[frame];
+            *duration = duration.saturating_add(FRAME_DURATION_STEP);
+        } else if ctx.is_key_pressed(KeyCode::Down) {
+            let duration = &mut layers.durations[frame];
+            *duration = duration
+                .saturating_sub(FRAME_DURATION_STEP)
+                .max(FRAME_DURATION_STEP);
+        } else if ctx.is_key_pressed(KeyCode::I) {
+            self.onion.shown = !self.onion.shown;
+            return true;
+        } else if ctx.is_key_pressed(KeyCode::A) {
+            // Playback starts from the frame being edited.
+            self.playback = match self.playback {
+                Some(_) => None,
+                None => Some((frame, Duration::ZERO)),
+            };
+        } else {
+            return false;
+        }
+        self.drag = None;
+        self.last_cell = None;
+        self.history.end_stroke();
+        true
+    }
+
+    /// Frames around the current one with their tint and opacity, the furthest first so the
+    /// nearest end up on top.
+    fn onion_skins(&self) -> Vec<(Sprite, RGBA8, u8)> {
+        if !self.onion.shown {
+            return Vec::new();
+        }
+        let frame = self.layers.frame;
+        let (past, future) = self.onion.tints;
+        let mut skins = Vec::new();
+        for distance in (1..=self.onion.frames).rev() {
+            let opacity = ONION_OPACITY >> (distance - 1).min(7);
+            if let Some(previous) = frame.checked_sub(distance) {
+                skins.push((self.layers.flatten(previous), past, opacity));
+            }
+            if frame + distance < self.layers.frame_count() {
+                skins.push((self.layers.flatten(frame + distance), future, opacity));
+            }
+        }
+        skins
+    }
+
+    /// Moves playback on by the time since the last update, skipping frames when it was long.
+    fn advance_playback(&mut self) {
+        let Some((frame, elapsed)) = self.playback else {
+            return;
+        };
+        let durations = &self.layers.durations;
+        let mut frame = frame.min(durations.len() - 1);
+        let mut elapsed = elapsed + self.clock.delta();
+        loop {
+            let duration = Duration::from_millis(u64::from(durations[frame].max(1)));
+            if elapsed < duration {
+                break;
+            }
+            elapsed -= duration;
+            frame = (frame + 1) % durations.len();
+        }
+        self.playback = Some((frame, elapsed));
+    }
+
+    fn handle_keys(&mut self, ctx: &Context) {
+        // Frame keys come first, shift+delete deletes a frame rather than a layer.
+        if self.handle_view_keys(ctx) || self.handle_frame_keys(ctx) || self.handle_layer_keys(ctx)
+        {
+            return;
+        }
+        let mods = ctx.get_key_mods();
+        if mods.ctrl && ctx.is_key_pressed(KeyCode::Z) {
+            let layers = &mut self.layers.layers;
+            let set = |(layer, frame): (usize, usize), index: usize, color| {
+                layers[layer].cels[frame].pixels[index] = color;
+            };
+            if mods.shift {
+                self.history.redo(set);
+            } else {
+                self.history.undo(set);
+            }
+        } else if ctx.is_key_pressed(KeyCode::P) {
+            self.select_tool(Tool::Pen);
+        } else if ctx.is_key_pressed(KeyCode::L) {
+            self.select_tool(Tool::Line);
+        } else if ctx.is_key_pressed(KeyCode::R) {
+            // Pressing again toggles between outline and filled.
+            let filled = self.tool == Tool::Rectangle { filled: false };
+            self.select_tool(Tool::Rectangle { filled });
+        } else if ctx.is_key_pressed(KeyCode::C) {
+            let filled = self.tool == Tool::Ellipse { filled: false };
+            self.select_tool(Tool::Ellipse { filled });
+        } else if ctx.is_key_pressed(KeyCode::B) {
+            // Pressing again toggles between contiguous and global fills.
+            if self.tool == Tool::Fill {
+                self.fill_options.global = !self.fill_options.global;
+            }
+            self.select_tool(Tool::Fill);
+        } else if ctx.is_key_pressed(KeyCode::K) {
+            self.fill_options.connectivity = match self.fill_options.connectivity {
+                Connectivity::Four => Connectivity::Eight,
+                Connectivity::Eight => Connectivity::Four,
+            };
+        } else if ctx.is_key_pressed(KeyCode::LeftBracket) {
+            self.fill_options.tolerance = self.fill_options.tolerance.saturating_sub(8);
+        } else if ctx.is_key_pressed(KeyCode::RightBracket) {
+            self.fill_options.tolerance = self.fill_options.tolerance.saturating_add(8);
+        } else if ctx.is_key_pressed(KeyCode::Equal) {
+            self.palette.add(self.selected_color);
+        } else if ctx.is_key_pressed(KeyCode::Minus) {
+            self.palette.remove(self.selected_color);
+        } else if let Some(index) = NUMBER_KEYS.iter().position(|&key| ctx.is_key_pressed(key)) {
+            if let Some(&color) = self.palette.colors.get(index) {
+                self.selected_color = color;
+            }
+        } else if ctx.is_key_pressed(KeyCode::G) {
+            // Shift toggles the guide grid instead of the pixel grid.
+            if mods.shift {
+                self.show_guide = !self.show_guide;
+            } else {
+                self.show_grid = !self.show_grid;
+            }
+        } else if ctx.is_key_pressed(KeyCode::V) {
+            self.preview_scale = self.preview_scale % 3 + 1;
+        } else if ctx.is_key_pressed(KeyCode::M) {
+            self.color_mode = self.color_mode.next();
+            self.mode_channels = None;
+        } else if ctx.is_key_pressed(KeyCode::O) {
+            self.selection_decrease();
+        } else if ctx.is_key_pressed(KeyCode::U) {
+            self.selection_increase();
+        } else if ctx.is_key_pressed(KeyCode::Period) {
+            self.step_color(step_size(mods));
+        } else if ctx.is_key_pressed(KeyCode::E) {
+            self.step_color(-step_size(mods));
+        } else if ctx.is_key_pressed(KeyCode::Enter) {
+            self.color_entry = Some(String::new());
+        }
+    }
+
+    /// Typing a color, enter sets it and escape cancels.
+    fn update_color_entry(&mut self, ctx: &Context) {
+        let Some(entry) = &mut self.color_entry else {
+            return;
+        };
+        if ctx.is_key_pressed(KeyCode::Escape) {
+            self.color_entry = None;
+        } else if ctx.is_key_pressed(KeyCode::Enter) || ctx.is_key_pressed(KeyCode::KpEnter) {
+            // Unparsable text stays to be corrected.
+            if let Some(color) = color::parse_color(entry) {
+                self.selected_color = color;
+                self.color_entry = None;
+            }
+        } else if ctx.is_key_pressed(KeyCode::Backspace) {
+            entry.pop();
+        } else if let Some(ch) = typed_char(ctx) {
+            entry.push(ch);
+        }
+    }
+
+    fn select_tool(&mut self, tool: Tool) {
+        self.tool = tool;
+        self.drag = None;
+        self.last_cell = None;
+        self.history.end_stroke();
+    }
+
+    fn paint_cell(&mut self, x: i32, y: i32, color: RGBA8) {
+        let index = (y as u32 * self.size.width + x as u32) as usize;
+        self.paint(index, color);
+    }
+
+    /// Sets a pixel of the active layer, remembering the change for undo.
+    fn paint(&mut self, index: usize, color: RGBA8) {
+        let cel = (self.layers.active, self.layers.frame);
+        let pixels = &mut self.layers.canvas_mut().pixels;
+        let before = pixels[index];
+        pixels[index] = color;
+        self.history.record(cel, index, before, color);
+    }
+
+    fn project(&self) -> Project {
+        let mut metadata = self.metadata.clone();
+        metadata.retain(|(key, _)| key != "software");
+        metadata.push(("software".to_string(), "pixel_editor".to_string()));
+        Project {
+            layers: self.layers.clone(),
+            palette: self.palette.colors.clone(),
+            selected_color: self.selected_color,
+            metadata,
+        }
+    }
+
+    fn select_swatch_at(&mut self, x: u32, y: u32) {
+        let panel_x = self.viewport.area.width;
+        let panel_height = self.viewport.area.height;
+        let len = self.palette.colors.len();
+        if let Some(index) = palette::swatch_at(x, y, panel_x, panel_height, len) {
+            self.selected_color = self.palette.colors[index];
+        }
+    }
+
     fn save(&self) {
-        if let Err(error) = save_sprite(&self.path, &self.canvas) {
-			println!("{error}");
+        // Projects keep the layers, frames and editor state, GIFs the flattened frames and
+        // other image formats a single layer of a single frame.
+        let saved = if is_project(&self.path) {
+            self.project().save(&self.path)
+        } else if let ImageFormat::Gif(encoding) = self.format {
+            let frames: Vec<Sprite> = (0..self.layers.frame_count())
+                .map(|frame| self.layers.flatten(frame))
+                .collect();
+            save_gif(&self.path, &frames, &self.layers.durations, encoding)
+        } else {
+            let frames = self.layers.frame_count();
+            if frames > 1 {
+                println!(
+                    "Only the first of {frames} frames is saved, use .gif or .pxe to keep them"
+                );
+            }
+            let image = self.layers.flatten(0);
+            if !self.format.keeps_alpha() && image.pixels.iter().any(|pixel| pixel.a != 255) {
+                println!(
+                    "Transparency isn't saved in this format, use .png, .pam or .pxe to keep it"
+                );
+            }
+            save_image(&self.path, &image, self.format)
+        };
+        if let Err(error) = saved {
+            println!("{error}");
+        }
+        if let Some((path, options)) = &self.sheet {
+            let frames: Vec<Sprite> = (0..self.layers.frame_count())
+                .map(|frame| self.layers.flatten(frame))
+                .collect();
+            let name = self
+                .path
+                .file_stem()
+                .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+            let layers = &self.layers;
+            if let Err(error) = export_sheet(
+                path,
+                &name,
+                &frames,
+                &layers.durations,
+                &layers.tags,
+                *options,
+            ) {
+                println!("Couldn't export the sheet {}: {error}", path.display());
+            }
+        }
+        if let (Some(path), true) = (&self.palette_path, self.palette.changed) {
+            if let Err(error) = self.palette.save(path) {
+                println!("Couldn't save the palette {}: {error}", path.display());
+            }
         }
     }
 
-    fn display_selected_color(&mut self, ctx: &mut Context) {
-        let RGBA8 { r, g, b, a: _ } = self.selected_color;
-        let (mut sr, mut sg, mut sb) = (' ',' ',' ');
+    /// Draws one canvas pixel, clipped to the view.
+    fn draw_cell(&self, ctx: &mut Context, cell: (i32, i32), pixel: RGBA8) {
+        let (x, y) = self.viewport.cell_to_screen(cell);
+        let zoom = self.viewport.zoom;
+        draw_checkered(ctx, (x, y), zoom, pixel, self.viewport.area);
+    }
+
+    /// Lines along the left and top edges of cells every `spacing` cells, and around the canvas.
+    fn draw_grid(&self, ctx: &mut Context, spacing: Size, color: RGBA8) {
+        let (columns, rows) = self.viewport.visible_cells(self.size);
+        let (left, top) = self.viewport.cell_to_screen((0, 0));
+        let zoom = self.viewport.zoom;
+        let area = self.viewport.area;
+        let vertical = Size::new(1, self.size.height * zoom);
+        let horizontal = Size::new(self.size.width * zoom, 1);
+        // The closing lines sit just outside the last column and row.
+        for x in columns.start..=columns.end {
+            if x % spacing.width == 0 || x == self.size.width {
+                let (x, _) = self.viewport.cell_to_screen((x as i32, 0));
+                draw_clipped(ctx, (x, top), vertical, color, area);
+            }
+        }
+        for y in rows.start..=rows.end {
+            if y % spacing.height == 0 || y == self.size.height {
+                let (_, y) = self.viewport.cell_to_screen((0, y as i32));
+                draw_clipped(ctx, (left, y), horizontal, color, area);
+            }
+        }
+    }
+
+    /// The canvas at 1x, 2x or 3x over the pane background, cut off where the window ends.
+    fn draw_preview(&self, ctx: &mut Context, image: &Sprite) {
+        let x = self.viewport.area.width + PANEL_WIDTH;
+        let height = self.viewport.area.height;
+        ctx.draw_rect(x, 0, self.preview_width, height, PANEL_BACKGROUND);
+        let pixels = image
+            .pixels
+            .iter()
+            .map(|&pixel| color::over(pixel, PANEL_BACKGROUND))
+            .collect();
+        let origin = Vec2::new((x + PREVIEW_MARGIN) as f32, PREVIEW_MARGIN as f32);
+        Sprite::new(origin, self.size, pixels)
+            .scaled(self.preview_scale)
+            .draw(ctx);
+    }
+
+    /// Swatches beside the canvas, the selected color is outlined.
+    fn draw_palette(&self, ctx: &mut Context) {
+        let panel_x = self.viewport.area.width;
+        let panel_height = self.viewport.area.height;
+        let window = Size::new(panel_x + PANEL_WIDTH, panel_height);
+        ctx.draw_rect(panel_x, 0, PANEL_WIDTH, panel_height, PANEL_BACKGROUND);
+        for (index, &color) in self.palette.colors.iter().enumerate() {
+            let Some((x, y)) = palette::swatch_position(index, panel_x, panel_height) else {
+                break;
+            };
+            if color == self.selected_color {
+                let outline = RGBA8::new(255, 255, 255, 255);
+                let around = Size::new(SWATCH_SIZE + 2, SWATCH_SIZE + 2);
+                draw_clipped(ctx, (x as i32 - 1, y as i32 - 1), around, outline, window);
+            }
+            draw_checkered(ctx, (x as i32, y as i32), SWATCH_SIZE, color, window);
+        }
+    }
+
+    fn display_selected_color(&self, ctx: &mut Context) {
+        let a = self.selected_color.a;
+        let mut markers = [' '; 4];
         match self.selection {
-        	Selection::R => { sr = '>'},
-        	Selection::G => { sg = '>'},
-        	Selection::B => { sb = '>'},
+            Selection::R => markers[0] = '>',
+            Selection::G => markers[1] = '>',
+            Selection::B => markers[2] = '>',
+            Selection::A => markers[3] = '>',
         }
-        let display_str = format!("color:{sr}r:{r},{sg}g:{g},{sb}b:{b}");
-        let pos = Vec2::new(10.0, (self.size.height * self.scale) as f32 - 20.0);
-        self.font.draw(ctx, &display_str, pos);
+        let tool = if self.tool == Tool::Fill {
+            format!("fill:{}", self.fill_options.describe())
+        } else {
+            self.tool.name().to_string()
+        };
+        let zoom = self.viewport.zoom;
+        let playing = if self.playback.is_some() {
+            " playing"
+        } else {
+            ""
+        };
+        let current = self.layers.frame;
+        let tags: String = self
+            .layers
+            .tags
+            .iter()
+            .filter(|tag| (tag.from..=tag.to).contains(&current))
+            .flat_map(|tag| [" ".to_string(), status_name(&tag.name)])
+            .collect();
+        let frame = format!(
+            "frame:{}/{} {}ms{tags}{playing}",
+            current + 1,
+            self.layers.frame_count(),
+            self.layers.durations[current]
+        );
+        let layer = self.layers.active();
+        let hidden = if layer.visible { "" } else { " hidden" };
+        let layer = format!(
+            "layer:{}/{} {} {} {}{hidden}",
+            self.layers.active + 1,
+            self.layers.layers.len(),
+            status_name(&layer.name),
+            layer.blend.name(),
+            layer.opacity
+        );
+        let (x, y) = self.cursor;
+        let hovered = self.pixel_at(self.cursor);
+        // The edited mode comes first with channel names and markers, the others follow.
+        let channels = self.mode_channels().map(f32::round);
+        let names = self.color_mode.channel_names();
+        let mut edited: Vec<String> = (0..3)
+            .map(|index| format!("{}{}:{}", markers[index], names[index], channels[index]))
+            .collect();
+        edited.push(format!("{}a:{a}", markers[3]));
+        let others: Vec<String> = [ColorMode::Rgb, ColorMode::Hsv, ColorMode::Hsl]
+            .into_iter()
+            .filter(|&mode| mode != self.color_mode)
+            .map(|mode| {
+                let [first, second, third] = mode.split(self.selected_color).map(f32::round);
+                format!("{}:{first},{second},{third}", mode.name())
+            })
+            .collect();
+        let mut display_str = format!(
+            "tool:{tool} zoom:{zoom}x {frame}\n{layer}\nat:{x},{y} {},{},{},{}\n{}:{}\n{}",
+            hovered.r,
+            hovered.g,
+            hovered.b,
+            hovered.a,
+            self.color_mode.name(),
+            edited.join(","),
+            others.join(" ")
+        );
+        if let Some(entry) = &self.color_entry {
+            display_str = format!("enter color:{entry}_\n{display_str}");
+        }
+        let lines = display_str.lines().count();
+        let top = (self.viewport.area.height as usize).saturating_sub(lines * CHAR_HEIGHT + 6);
+        self.font
+            .draw(ctx, &display_str, &Vec2::new(10.0, top as f32));
     }
 
     fn selection_increase(&mut self) {
         self.selection = match self.selection {
             Selection::R => Selection::G,
             Selection::G => Selection::B,
-            Selection::B => Selection::R,
+            Selection::B => Selection::A,
+            Selection::A => Selection::R,
         }
     }
-    
+
     fn selection_decrease(&mut self) {
         self.selection = match self.selection {
-            Selection::R => Selection::B,
+            Selection::R => Selection::A,
             Selection::G => Selection::R,
             Selection::B => Selection::G,
+            Selection::A => Selection::B,
         }
     }
-    fn color_increase(&mut self) {
-        let RGBA8 {
-            mut r,
-            mut g,
-            mut b,
-            a,
-        } = self.selected_color;
-
-        match self.selection {
-            Selection::R => {
-                r = r.saturating_add(10);
-            }
-            Selection::G => {
-                g = g.saturating_add(10);
-            }
-            Selection::B => {
-                b = b.saturating_add(10);
-            }
+    /// Selected color split into the channels of the color mode.
+    fn mode_channels(&self) -> [f32; 3] {
+        match self.mode_channels {
+            Some((color, channels)) if color == self.selected_color => channels,
+            _ => self.color_mode.split(self.selected_color),
         }
-        self.selected_color = RGBA8::new(r, g, b, a);
     }
-    fn color_decrease(&mut self) {
-        let RGBA8 {
-            mut r,
-            mut g,
-            mut b,
-            a,
-        } = self.selected_color;
 
-        match self.selection {
-            Selection::R => {
-                r = r.saturating_sub(10);
-            }
-            Selection::G => {
-                g = g.saturating_sub(10);
+    fn step_color(&mut self, step: f32) {
+        let channel = match self.selection {
+            Selection::R => 0,
+            Selection::G => 1,
+            Selection::B => 2,
+            Selection::A => {
+                let alpha = (f32::from(self.selected_color.a) + step).clamp(0.0, 255.0);
+                self.selected_color.a = alpha as u8;
+                return;
             }
-            Selection::B => {
-                b = b.saturating_sub(10);
-            }
-        }
-        self.selected_color = RGBA8::new(r, g, b, a);}
+        };
+        let channels = self.color_mode.step(self.mode_channels(), channel, step);
+        self.selected_color = self.color_mode.join(channels, self.selected_color.a);
+        self.mode_channels = Some((self.selected_color, channels));
+    }
+}
+
+/// Ctrl steps colors by 1 and shift by 32 instead of 10.
+fn step_size(mods: KeyMods) -> f32 {
+    if mods.ctrl {
+        1.0
+    } else if mods.shift {
+        32.0
+    } else {
+        10.0
+    }
+}
+
+/// Character for the key pressed this frame, only those that can be part of a color.
+fn typed_char(ctx: &Context) -> Option<char> {
+    if ctx.get_key_mods().shift && ctx.is_key_pressed(KeyCode::Key3) {
+        return Some('#');
+    }
+    TYPED_KEYS
+        .iter()
+        .find(|(key, _)| ctx.is_key_pressed(*key))
+        .map(|&(_, ch)| ch)
+}
+
+/// Name from a file or the command line as a single line of the status text, the font draws
+/// other characters it has no letter for as placeholders.
+fn status_name(name: &str) -> String {
+    name.chars()
+        .map(|ch| if ch.is_control() { ' ' } else { ch })
+        .collect()
+}
+
+/// Draws a square of `size`, transparent colors over a checkerboard of half squares.
+fn draw_checkered(ctx: &mut Context, (x, y): (i32, i32), size: u32, pixel: RGBA8, clip: Size) {
+    let light = color::over(pixel, CHECKER_LIGHT);
+    // Single pixels show the light squares only.
+    if pixel.a == 255 || size < 2 {
+        draw_clipped(ctx, (x, y), Size::new(size, size), light, clip);
+        return;
+    }
+    let half = size / 2;
+    let rest = size - half;
+    let dark = color::over(pixel, CHECKER_DARK);
+    draw_clipped(ctx, (x, y), Size::new(size, size), light, clip);
+    draw_clipped(ctx, (x + half as i32, y), Size::new(rest, half), dark, clip);
+    draw_clipped(ctx, (x, y + half as i32), Size::new(half, rest), dark, clip);
+}
+
+/// Draws the part of a rectangle inside of `clip`, measured from the top left of the window.
+fn draw_clipped(ctx: &mut Context, (x, y): (i32, i32), size: Size, color: RGBA8, clip: Size) {
+    let (left, top) = (x.max(0), y.max(0));
+    let right = (x + size.width as i32).min(clip.width as i32);
+    let bottom = (y + size.height as i32).min(clip.height as i32);
+    if left < right && top < bottom {
+        let (width, height) = ((right - left) as u32, (bottom - top) as u32);
+        ctx.draw_rect(left as u32, top as u32, width, height, color);
+    }
 }
@@ -0,0 +1,317 @@
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use simple_pixels::rgb::RGBA8;
+
+use crate::color;
+
+pub const SWATCH_SIZE: u32 = 16;
+const SWATCH_GAP: u32 = 2;
+const PANEL_COLUMNS: u32 = 2;
+/// Width of the swatch strip drawn beside the canvas.
+pub const PANEL_WIDTH: u32 = PANEL_COLUMNS * (SWATCH_SIZE + SWATCH_GAP) + SWATCH_GAP;
+
+/// Palette files understood by the loader and the saver, picked by extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PaletteFormat {
+    /// `.gpl`
+    Gimp,
+    /// `.txt`, `AARRGGBB` per line.
+    PaintNet,
+    /// `.pal`
+    Jasc,
+    /// `.hex` or anything else, `RRGGBB` per line.
+    Hex,
+}
+
+impl PaletteFormat {
+    fn for_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("gpl") => Self::Gimp,
+            Some("txt") => Self::PaintNet,
+            Some("pal") => Self::Jasc,
+            _ => Self::Hex,
+        }
+    }
+}
+
+fn invalid(reason: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+fn parse_hex(hex: &str) -> Result<RGBA8, io::Error> {
+    color::from_hex(hex).ok_or_else(|| invalid(format!("Couldn't parse color {hex}")))
+}
+
+/// Paint.NET puts alpha first.
+fn parse_argb(hex: &str) -> Result<RGBA8, io::Error> {
+    let RGBA8 {
+        r: a,
+        g: r,
+        b: g,
+        a: b,
+    } = parse_hex(hex)?;
+    if hex.trim_start_matches('#').len() == 8 {
+        Ok(RGBA8::new(r, g, b, a))
+    } else {
+        Err(invalid(format!(
+            "Couldn't parse color {hex}, expected AARRGGBB"
+        )))
+    }
+}
+
+fn parse_rgb<'a>(mut fields: impl Iterator<Item = &'a str>) -> Result<RGBA8, io::Error> {
+    let mut channel = || {
+        let field = fields
+            .next()
+            .ok_or_else(|| invalid("Missing color channel".to_string()))?;
+        field
+            .parse::<u8>()
+            .map_err(|_| invalid(format!("Couldn't parse color channel {field}")))
+    };
+    Ok(RGBA8::new(channel()?, channel()?, channel()?, 255))
+}
+
+pub struct Palette {
+    pub colors: Vec<RGBA8>,
+    /// Set when colors were added or removed since loading.
+    pub changed: bool,
+}
+
+impl Default for Palette {
+    /// The pico-8 colors.
+    fn default() -> Self {
+        let colors = [
+            (0, 0, 0),
+            (29, 43, 83),
+            (126, 37, 83),
+            (0, 135, 81),
+            (171, 82, 54),
+            (95, 87, 79),
+            (194, 195, 199),
+            (255, 241, 232),
+            (255, 0, 77),
+            (255, 163, 0),
+            (255, 236, 39),
+            (0, 228, 54),
+            (41, 173, 255),
+            (131, 118, 156),
+            (255, 119, 168),
+            (255, 204, 170),
+        ]
+        .into_iter()
+        .map(|(r, g, b)| RGBA8::new(r, g, b, 255))
+        .collect();
+        Self {
+            colors,
+            changed: false,
+        }
+    }
+}
+
+impl Palette {
+    pub fn load(path: &Path) -> Result<Self, io::Error> {
+        let data = fs::read_to_string(path)?;
+        let mut lines = data.lines().map(str::trim).filter(|line| !line.is_empty());
+        let mut colors = Vec::new();
+        match PaletteFormat::for_path(path) {
+            PaletteFormat::Gimp => {
+                if lines.next() != Some("GIMP Palette") {
+                    return Err(invalid("Missing GIMP Palette header".to_string()));
+                }
+                for line in lines {
+                    let is_header = line.starts_with("Name:") || line.starts_with("Columns:");
+                    if line.starts_with('#') || is_header {
+                        continue;
+                    }
+                    colors.push(parse_rgb(line.split_whitespace())?);
+                }
+            }
+            PaletteFormat::PaintNet => {
+                for line in lines.filter(|line| !line.starts_with(';')) {
+                    colors.push(parse_argb(line)?);
+                }
+            }
+            PaletteFormat::Jasc => {
+                if lines.next() != Some("JASC-PAL") {
+                    return Err(invalid("Missing JASC-PAL header".to_string()));
+                }
+                let _version = lines.next();
+                let count = lines
+                    .next()
+                    .and_then(|count| count.parse::<usize>().ok())
+                    .ok_or_else(|| invalid("Missing color count".to_string()))?;
+                for line in lines.take(count) {
+                    colors.push(parse_rgb(line.split_whitespace())?);
+                }
+            }
+            PaletteFormat::Hex => {
+                for line in lines.filter(|line| !line.starts_with(';')) {
+                    colors.push(parse_hex(line)?);
+                }
+            }
+        }
+        Ok(Self {
+            colors,
+            changed: false,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), io::Error> {
+        let mut data = String::new();
+        let name = path
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .unwrap_or("palette");
+        // Writing to a string can't fail.
+        match PaletteFormat::for_path(path) {
+            PaletteFormat::Gimp => {
+                let _ = write!(data, "GIMP Palette\nName: {name}\nColumns: 0\n#\n");
+                for RGBA8 { r, g, b, a: _ } in &self.colors {
+                    let _ = writeln!(data, "{r:>3} {g:>3} {b:>3}\t#{r:02x}{g:02x}{b:02x}");
+                }
+            }
+            PaletteFormat::PaintNet => {
+                let _ = writeln!(data, "; paint.net Palette File\n; {name}");
+                for RGBA8 { r, g, b, a } in &self.colors {
+                    let _ = writeln!(data, "{a:02X}{r:02X}{g:02X}{b:02X}");
+                }
+            }
+            PaletteFormat::Jasc => {
+                let _ = write!(data, "JASC-PAL\n0100\n{}\n", self.colors.len());
+                for RGBA8 { r, g, b, a: _ } in &self.colors {
+                    let _ = writeln!(data, "{r} {g} {b}");
+                }
+            }
+            PaletteFormat::Hex => {
+                for RGBA8 { r, g, b, a } in &self.colors {
+                    if *a == 255 {
+                        let _ = writeln!(data, "{r:02x}{g:02x}{b:02x}");
+                    } else {
+                        let _ = writeln!(data, "{r:02x}{g:02x}{b:02x}{a:02x}");
+                    }
+                }
+            }
+        }
+        fs::write(path, data)
+    }
+
+    pub fn add(&mut self, color: RGBA8) {
+        if !self.colors.contains(&color) {
+            self.colors.push(color);
+            self.changed = true;
+        }
+    }
+
+    pub fn remove(&mut self, color: RGBA8) {
+        let len = self.colors.len();
+        self.colors.retain(|&swatch| swatch != color);
+        self.changed |= self.colors.len() != len;
+    }
+}
+
+/// Top left corner of a swatch in a panel starting at `panel_x`, `None` if it doesn't fit.
+pub fn swatch_position(index: usize, panel_x: u32, panel_height: u32) -> Option<(u32, u32)> {
+    let index = index as u32;
+    let (column, row) = (index % PANEL_COLUMNS, index / PANEL_COLUMNS);
+    let x = panel_x + SWATCH_GAP + column * (SWATCH_SIZE + SWATCH_GAP);
+    let y = SWATCH_GAP + row * (SWATCH_SIZE + SWATCH_GAP);
+    (y + SWATCH_SIZE <= panel_height).then_some((x, y))
+}
+
+/// Index of the swatch under a point of the window.
+pub fn swatch_at(x: u32, y: u32, panel_x: u32, panel_height: u32, len: usize) -> Option<usize> {
+    (0..len).find(|&index| {
+        swatch_position(index, panel_x, panel_height).is_some_and(|(swatch_x, swatch_y)| {
+            (swatch_x..swatch_x + SWATCH_SIZE).contains(&x)
+                && (swatch_y..swatch_y + SWATCH_SIZE).contains(&y)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("pixel_editor_{}_{name}", std::process::id()))
+    }
+
+    fn load_text(name: &str, text: &str) -> Result<Vec<RGBA8>, io::Error> {
+        let path = temp_path(name);
+        fs::write(&path, text).unwrap();
+        let palette = Palette::load(&path);
+        fs::remove_file(&path).unwrap();
+        palette.map(|palette| palette.colors)
+    }
+
+    #[test]
+    fn round_trips() {
+        let opaque = vec![
+            RGBA8::new(0, 0, 0, 255),
+            RGBA8::new(255, 128, 7, 255),
+            RGBA8::new(18, 52, 86, 255),
+        ];
+        let mut transparent = opaque.clone();
+        transparent.push(RGBA8::new(1, 2, 3, 4));
+        for (name, colors) in [
+            ("round_trip.gpl", &opaque),
+            ("round_trip.pal", &opaque),
+            ("round_trip.txt", &transparent),
+            ("round_trip.hex", &transparent),
+        ] {
+            let path = temp_path(name);
+            let palette = Palette {
+                colors: colors.clone(),
+                changed: true,
+            };
+            palette.save(&path).unwrap();
+            let loaded = Palette::load(&path);
+            fs::remove_file(&path).unwrap();
+            let loaded = loaded.unwrap();
+            assert_eq!(&loaded.colors, colors, "{name}");
+            assert!(!loaded.changed);
+        }
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let red = RGBA8::new(255, 0, 0, 255);
+        let blue = RGBA8::new(0, 0, 255, 128);
+        let gimp = "GIMP Palette\nName: test\nColumns: 4\n\n# comment\n255   0   0\tred\n\n";
+        assert_eq!(load_text("comments.gpl", gimp).unwrap(), vec![red]);
+        let paint_net = "; paint.net Palette File\n\n;comment\nFFFF0000\n  800000FF  \n";
+        assert_eq!(
+            load_text("comments.txt", paint_net).unwrap(),
+            vec![red, blue]
+        );
+        let jasc = "JASC-PAL\r\n0100\r\n1\r\n\r\n255 0 0\r\n";
+        assert_eq!(load_text("comments.pal", jasc).unwrap(), vec![red]);
+        let hex = ";comment\n\n#ff0000\n0000ff80\n";
+        assert_eq!(load_text("comments.hex", hex).unwrap(), vec![red, blue]);
+    }
+
+    #[test]
+    fn malformed_palettes_are_errors() {
+        for (name, text) in [
+            ("missing_header.gpl", "255 0 0\n"),
+            ("missing_channel.gpl", "GIMP Palette\n255 0\n"),
+            ("channel_range.gpl", "GIMP Palette\n256 0 0\n"),
+            ("rgb.txt", "FF0000\n"),
+            ("missing_header.pal", "0100\n1\n255 0 0\n"),
+            ("missing_count.pal", "JASC-PAL\n0100\n"),
+            ("word.pal", "JASC-PAL\n0100\n1\nred green blue\n"),
+            ("word.hex", "red\n"),
+        ] {
+            let error = load_text(name, text).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{name}");
+        }
+    }
+}
@@ -1,75 +1,636 @@
+//! Basic image saving/loading with ppt format.
+
+use std::ffi::OsStr;
+use std::fmt::Write;
 use std::fs;
 use std::io;
-use std::path::{PathBuf};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use simple_pixels::rgb::RGBA8;
 
+use crate::error::ImageError;
 use crate::{Size, Sprite, Vec2};
 
-/// Basic image saving/loading with ppt format.
+/// Netpbm variants understood by the loader and the saver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PptFormat {
+    /// ASCII black and white.
+    P1,
+    /// ASCII grayscale.
+    P2,
+    /// ASCII rgb.
+    P3,
+    /// Binary black and white.
+    P4,
+    /// Binary grayscale.
+    P5,
+    /// Binary rgb.
+    P6,
+    /// Binary PAM, read as grayscale or rgb with optional alpha, written as rgb with alpha.
+    P7,
+}
+
+impl PptFormat {
+    pub fn from_magic(magic: &str) -> Option<Self> {
+        match magic {
+            "P1" => Some(Self::P1),
+            "P2" => Some(Self::P2),
+            "P3" => Some(Self::P3),
+            "P4" => Some(Self::P4),
+            "P5" => Some(Self::P5),
+            "P6" => Some(Self::P6),
+            "P7" => Some(Self::P7),
+            _ => None,
+        }
+    }
+
+    pub fn magic(self) -> &'static str {
+        match self {
+            Self::P1 => "P1",
+            Self::P2 => "P2",
+            Self::P3 => "P3",
+            Self::P4 => "P4",
+            Self::P5 => "P5",
+            Self::P6 => "P6",
+            Self::P7 => "P7",
+        }
+    }
+
+    /// Bitmaps have no maxval in their header.
+    fn is_bitmap(self) -> bool {
+        matches!(self, Self::P1 | Self::P4)
+    }
+
+    /// Channels written by the saver, PAM files can have any depth up to 4 when loaded.
+    fn channels(self) -> usize {
+        match self {
+            Self::P1 | Self::P2 | Self::P4 | Self::P5 => 1,
+            Self::P3 | Self::P6 => 3,
+            Self::P7 => 4,
+        }
+    }
+}
+
+impl FromStr for PptFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_magic(&s.to_ascii_uppercase()).ok_or_else(|| format!("Unknown ppt format {s}"))
+    }
+}
+
+/// Splits the textual parts of a netpbm file into tokens, skipping `#` comments.
+struct Tokenizer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&byte) = self.data.get(self.pos) {
+            if byte == b'#' {
+                while let Some(&byte) = self.data.get(self.pos) {
+                    if byte == b'\n' || byte == b'\r' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Option<&'a str> {
+        self.skip_whitespace_and_comments();
+        let start = self.pos;
+        while let Some(&byte) = self.data.get(self.pos) {
+            if byte.is_ascii_whitespace() || byte == b'#' {
+                break;
+            }
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return None;
+        }
+        std::str::from_utf8(&self.data[start..self.pos]).ok()
+    }
 
-pub fn save_sprite(path: &PathBuf, sprite: &Sprite) -> Result<(), io::Error> {
+    /// Every sample takes at least a byte, so the header can't make us allocate more than this.
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    /// Plain bitmaps don't need whitespace between the samples.
+    fn next_bit(&mut self) -> Option<u8> {
+        self.skip_whitespace_and_comments();
+        let byte = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    /// Binary rasters start after exactly one whitespace character following the header.
+    fn binary_rest(self) -> &'a [u8] {
+        match self.data.get(self.pos) {
+            Some(byte) if byte.is_ascii_whitespace() => &self.data[self.pos + 1..],
+            _ => &self.data[self.pos..],
+        }
+    }
+}
+
+/// How a sprite is encoded on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PptEncoding {
+    pub format: PptFormat,
+    /// Largest sample value, samples above 255 take two big-endian bytes in binary formats.
+    pub maxval: u16,
+}
+
+impl PptEncoding {
+    pub fn new(format: PptFormat, maxval: u16) -> Self {
+        assert!(maxval > 0, "maxval must be at least 1");
+        let maxval = if format.is_bitmap() { 1 } else { maxval };
+        Self { format, maxval }
+    }
+
+    /// Encoding for new files, `.pam` files keep transparency.
+    pub fn for_path(path: &Path) -> Self {
+        let pam = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("pam"));
+        if pam {
+            Self::new(PptFormat::P7, 255)
+        } else {
+            Self::default()
+        }
+    }
+
+    fn is_wide(self) -> bool {
+        self.maxval > 255
+    }
+
+    /// Rescales a sample from 0..=maxval into 0..=255.
+    fn expand(self, sample: u16) -> u8 {
+        let maxval = u32::from(self.maxval);
+        let sample = u32::from(sample);
+        ((sample * 255 + maxval / 2) / maxval) as u8
+    }
+
+    /// Rescales a sample from 0..=255 into 0..=maxval.
+    fn quantize(self, sample: u8) -> u16 {
+        let maxval = u32::from(self.maxval);
+        ((u32::from(sample) * maxval + 127) / 255) as u16
+    }
+}
+
+impl Default for PptEncoding {
+    fn default() -> Self {
+        Self::new(PptFormat::P3, 255)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Header {
+    encoding: PptEncoding,
+    width: usize,
+    height: usize,
+    /// Gray, gray and alpha, rgb or rgb and alpha.
+    channels: usize,
+}
+
+fn header_field<T: FromStr>(tokens: &mut Tokenizer, name: &str) -> Result<T, ImageError> {
+    let token = tokens
+        .next_token()
+        .ok_or_else(|| ImageError::BadHeader(format!("no {name}")))?;
+    token
+        .parse::<T>()
+        .map_err(|_| ImageError::BadHeader(format!("couldn't parse {name} from {token:?}")))
+}
+
+fn parse_header(tokens: &mut Tokenizer) -> Result<Header, ImageError> {
+    let magic = tokens
+        .next_token()
+        .ok_or_else(|| ImageError::BadHeader("no format".to_string()))?;
+    let format = PptFormat::from_magic(magic)
+        .ok_or_else(|| ImageError::UnsupportedFormat(magic.to_string()))?;
+    if format == PptFormat::P7 {
+        return parse_pam_header(tokens);
+    }
+    let width: u32 = header_field(tokens, "sprite width")?;
+    let height: u32 = header_field(tokens, "sprite height")?;
+    let maxval = if format.is_bitmap() {
+        1
+    } else {
+        header_field(tokens, "max colors")?
+    };
+    check_header(width, height, maxval)?;
+    Ok(Header {
+        encoding: PptEncoding::new(format, maxval),
+        width: width as usize,
+        height: height as usize,
+        channels: format.channels(),
+    })
+}
+
+/// PAM headers are `KEYWORD value` lines ending with `ENDHDR`.
+fn parse_pam_header(tokens: &mut Tokenizer) -> Result<Header, ImageError> {
+    let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+    let mut tupltype = None;
+    loop {
+        let keyword = tokens
+            .next_token()
+            .ok_or_else(|| ImageError::BadHeader("no ENDHDR".to_string()))?;
+        match keyword {
+            "WIDTH" => width = Some(header_field::<u32>(tokens, "sprite width")?),
+            "HEIGHT" => height = Some(header_field::<u32>(tokens, "sprite height")?),
+            "DEPTH" => depth = Some(header_field::<usize>(tokens, "depth")?),
+            "MAXVAL" => maxval = Some(header_field::<u16>(tokens, "max colors")?),
+            "TUPLTYPE" => tupltype = Some(header_field::<String>(tokens, "tuple type")?),
+            "ENDHDR" => break,
+            _ => return Err(ImageError::BadHeader(format!("unknown field {keyword}"))),
+        }
+    }
+    let width = width.ok_or_else(|| ImageError::BadHeader("no sprite width".to_string()))?;
+    let height = height.ok_or_else(|| ImageError::BadHeader("no sprite height".to_string()))?;
+    let depth = depth.ok_or_else(|| ImageError::BadHeader("no depth".to_string()))?;
+    let maxval = maxval.ok_or_else(|| ImageError::BadHeader("no max colors".to_string()))?;
+    check_header(width, height, maxval)?;
+    let expected_depth = match tupltype.as_deref() {
+        None => depth,
+        Some("BLACKANDWHITE" | "GRAYSCALE") => 1,
+        Some("BLACKANDWHITE_ALPHA" | "GRAYSCALE_ALPHA") => 2,
+        Some("RGB") => 3,
+        Some("RGB_ALPHA") => 4,
+        Some(tupltype) => return Err(ImageError::UnsupportedFormat(format!("P7 {tupltype}"))),
+    };
+    if depth != expected_depth || !(1..=4).contains(&depth) {
+        return Err(ImageError::UnsupportedFormat(format!(
+            "P7 with depth {depth} and tuple type {}",
+            tupltype.as_deref().unwrap_or("none")
+        )));
+    }
+    Ok(Header {
+        encoding: PptEncoding::new(PptFormat::P7, maxval),
+        width: width as usize,
+        height: height as usize,
+        channels: depth,
+    })
+}
+
+fn check_header(width: u32, height: u32, maxval: u16) -> Result<(), ImageError> {
+    if width == 0 || height == 0 {
+        return Err(ImageError::BadHeader(format!(
+            "empty sprite {width}x{height}"
+        )));
+    }
+    if maxval == 0 {
+        return Err(ImageError::BadHeader("max colors is 0".to_string()));
+    }
+    Ok(())
+}
+
+/// Grayscale value of the pixel, used when saving to P1, P2, P4 and P5.
+fn luma(pixel: RGBA8) -> u8 {
+    let luma = u32::from(pixel.r) * 299 + u32::from(pixel.g) * 587 + u32::from(pixel.b) * 114;
+    (luma / 1000) as u8
+}
+
+/// In bitmaps 1 is black.
+fn bit(pixel: RGBA8) -> u8 {
+    u8::from(luma(pixel) < 128)
+}
+
+pub fn save_sprite(
+    path: &PathBuf,
+    sprite: &Sprite,
+    encoding: PptEncoding,
+) -> Result<(), io::Error> {
     let (width, height) = (sprite.size.width, sprite.size.height);
-    let mut data: String = String::with_capacity(sprite.pixels.len() * 3 * 4); // 3 colors, 4 chars per color string (max)
-    for pixel in &sprite.pixels {
-        data.push_str(&format!("{} {} {} ", pixel.r, pixel.g, pixel.b));
-    }
-    let ppt_header = format!("P3 {width} {height} 255\n");
-    fs::write(path, [ppt_header, data].concat())
-}
-
-pub fn load_sprite(path: &PathBuf) -> Result<Sprite, io::Error> {
-    let data = fs::read_to_string(path)?;
-    if let Some((header, data)) = data.split_once('\n') {
-        let data: Vec<&str> = data.split_ascii_whitespace().collect();
-        let mut split = header.split_whitespace();
-        let format = split
-            .next()
-            .expect("Error while parsing ppt sprite: no format!");
-        assert!(format == "P3", "Only support P3 ppt version");
-        let width = split
-            .next()
-            .expect("Error while parsing ppt sprite: no sprite width!")
-            .parse::<usize>()
-            .expect("couldn't parse sprite width");
-        let height = split
-            .next()
-            .expect("Error while parsing ppt sprite: no sprite height!")
-            .parse::<usize>()
-            .expect("couldn't parse sprite height");
-        let colors = split
-            .next()
-            .expect("Error while parsing ppt sprite: no max colors!");
-        assert!(colors == "255", "Not yet support anything but 255 colors");
-        assert!(split.next().is_none(), "Unknown additional header fields");
-
-        let mut pixels: Vec<RGBA8> = Vec::with_capacity(width * height);
-        for y in 0..height {
-            for x in 0..width {
-                let r = data[y * width * 3 + x * 3];
-                let g = data[y * width * 3 + x * 3 + 1];
-                let b = data[y * width * 3 + x * 3 + 2];
-                let a = 255;
-                pixels.push(RGBA8::new(
-                    r.parse::<u8>().unwrap(),
-                    g.parse::<u8>().unwrap(),
-                    b.parse::<u8>().unwrap(),
-                    a,
-                ));
+    let PptEncoding { format, maxval } = encoding;
+    let magic = format.magic();
+    let ppt_header = match format {
+        PptFormat::P1 | PptFormat::P4 => format!("{magic} {width} {height}\n"),
+        PptFormat::P7 => format!(
+            "{magic}\nWIDTH {width}\nHEIGHT {height}\nDEPTH 4\nMAXVAL {maxval}\nTUPLTYPE RGB_ALPHA\nENDHDR\n"
+        ),
+        _ => format!("{magic} {width} {height} {maxval}\n"),
+    };
+    let mut data: Vec<u8> = ppt_header.into_bytes();
+    let samples = |pixel: &RGBA8| -> Vec<u16> {
+        match format.channels() {
+            1 => vec![encoding.quantize(luma(*pixel))],
+            3 => vec![
+                encoding.quantize(pixel.r),
+                encoding.quantize(pixel.g),
+                encoding.quantize(pixel.b),
+            ],
+            _ => vec![
+                encoding.quantize(pixel.r),
+                encoding.quantize(pixel.g),
+                encoding.quantize(pixel.b),
+                encoding.quantize(pixel.a),
+            ],
+        }
+    };
+    match format {
+        PptFormat::P4 => {
+            for row in sprite.pixels.chunks(width as usize) {
+                for byte in row.chunks(8) {
+                    let packed = byte
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |acc, (i, pixel)| acc | bit(*pixel) << (7 - i));
+                    data.push(packed);
+                }
+            }
+        }
+        PptFormat::P5 | PptFormat::P6 | PptFormat::P7 => {
+            for sample in sprite.pixels.iter().flat_map(samples) {
+                if encoding.is_wide() {
+                    data.extend_from_slice(&sample.to_be_bytes());
+                } else {
+                    data.push(sample as u8);
+                }
+            }
+        }
+        PptFormat::P1 | PptFormat::P2 | PptFormat::P3 => {
+            // channels, 6 chars per color string (max)
+            let mut text = String::with_capacity(sprite.pixels.len() * format.channels() * 6);
+            for pixel in &sprite.pixels {
+                if format == PptFormat::P1 {
+                    write!(text, "{} ", bit(*pixel)).expect("Writing to a string can't fail");
+                    continue;
+                }
+                for sample in samples(pixel) {
+                    write!(text, "{sample} ").expect("Writing to a string can't fail");
+                }
+            }
+            data.extend_from_slice(text.as_bytes());
+        }
+    }
+    fs::write(path, data)
+}
+
+pub fn load_sprite(path: &PathBuf) -> Result<(Sprite, PptEncoding), ImageError> {
+    let data = fs::read(path)?;
+    decode_sprite(&data)
+}
+
+fn check_length<T>(samples: &[T], expected: usize) -> Result<(), ImageError> {
+    if samples.len() < expected {
+        return Err(ImageError::TruncatedData {
+            expected,
+            found: samples.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Raw samples in 0..=maxval, bitmaps are inverted so that 1 is white like in the other formats.
+fn read_samples(mut tokens: Tokenizer, header: &Header) -> Result<Vec<u16>, ImageError> {
+    let Header {
+        encoding,
+        width,
+        height,
+        channels,
+    } = *header;
+    let format = encoding.format;
+    let maxval = encoding.maxval;
+
+    let expected = width
+        .checked_mul(height)
+        .and_then(|area| area.checked_mul(channels))
+        .ok_or_else(|| ImageError::BadHeader(format!("sprite {width}x{height} is too large")))?;
+    let samples: Vec<u16> = match format {
+        PptFormat::P1 => {
+            let mut samples = Vec::with_capacity(expected.min(tokens.remaining()));
+            for index in 0..expected {
+                let sample = match tokens.next_bit() {
+                    Some(b'0') => 1,
+                    Some(b'1') => 0,
+                    Some(other) => {
+                        return Err(ImageError::SampleOutOfRange {
+                            index,
+                            sample: char::from(other).to_string(),
+                            maxval,
+                        })
+                    }
+                    None => {
+                        return Err(ImageError::TruncatedData {
+                            expected,
+                            found: index,
+                        })
+                    }
+                };
+                samples.push(sample);
             }
+            samples
         }
+        PptFormat::P4 => {
+            let row_bytes = width.div_ceil(8);
+            let raster = tokens.binary_rest();
+            check_length(raster, row_bytes * height)?;
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (y, x)))
+                .map(|(y, x)| {
+                    let byte = raster[y * row_bytes + x / 8];
+                    u16::from(byte >> (7 - x % 8) & 1 == 0)
+                })
+                .collect()
+        }
+        PptFormat::P5 | PptFormat::P6 | PptFormat::P7 => {
+            let raster = tokens.binary_rest();
+            let samples: Vec<u16> = if encoding.is_wide() {
+                raster
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect()
+            } else {
+                raster.iter().map(|&sample| u16::from(sample)).collect()
+            };
+            check_length(&samples, expected)?;
+            samples
+        }
+        PptFormat::P2 | PptFormat::P3 => {
+            let mut samples = Vec::with_capacity(expected.min(tokens.remaining()));
+            for index in 0..expected {
+                let token = tokens.next_token().ok_or(ImageError::TruncatedData {
+                    expected,
+                    found: index,
+                })?;
+                let sample = token
+                    .parse::<u16>()
+                    .map_err(|_| ImageError::SampleOutOfRange {
+                        index,
+                        sample: token.to_string(),
+                        maxval,
+                    })?;
+                samples.push(sample);
+            }
+            samples
+        }
+    };
+    if let Some((index, sample)) = samples
+        .iter()
+        .take(expected)
+        .enumerate()
+        .find(|(_, &sample)| sample > maxval)
+    {
+        return Err(ImageError::SampleOutOfRange {
+            index,
+            sample: sample.to_string(),
+            maxval,
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Decodes any of the supported netpbm variants from the file contents.
+pub fn decode_sprite(data: &[u8]) -> Result<(Sprite, PptEncoding), ImageError> {
+    let mut tokens = Tokenizer::new(data);
+    let header = parse_header(&mut tokens)?;
+    let samples = read_samples(tokens, &header)?;
+    let Header {
+        encoding,
+        width,
+        height,
+        channels,
+    } = header;
 
-        return Ok(Sprite::new(
-            Vec2::new(0.0, 0.0),
-            Size::new(width.try_into().unwrap(), height.try_into().unwrap()),
-            pixels,
-        ));
+    let mut pixels: Vec<RGBA8> = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let offset = (y * width + x) * channels;
+            let sample = |i: usize| encoding.expand(samples[offset + i]);
+            let pixel = match channels {
+                1 => RGBA8::new(sample(0), sample(0), sample(0), 255),
+                2 => RGBA8::new(sample(0), sample(0), sample(0), sample(1)),
+                3 => RGBA8::new(sample(0), sample(1), sample(2), 255),
+                _ => RGBA8::new(sample(0), sample(1), sample(2), sample(3)),
+            };
+            pixels.push(pixel);
+        }
     }
 
-    Err(io::Error::new(
-        io::ErrorKind::InvalidData,
-        "Couldn't split file on newline!",
-    ))
+    let sprite = Sprite::new(
+        Vec2::new(0.0, 0.0),
+        Size::new(width as u32, height as u32),
+        pixels,
+    );
+    Ok((sprite, encoding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [PptFormat; 7] = [
+        PptFormat::P1,
+        PptFormat::P2,
+        PptFormat::P3,
+        PptFormat::P4,
+        PptFormat::P5,
+        PptFormat::P6,
+        PptFormat::P7,
+    ];
+
+    /// Colors every format can keep at `encoding`: black and white for bitmaps, grays for
+    /// grayscale and transparency only for PAM.
+    fn sprite(encoding: PptEncoding) -> Sprite {
+        // 11 pixels wide, so that bitmap rows end in a partial byte.
+        let size = Size::new(11, 3);
+        let keep = |sample: u8| encoding.expand(encoding.quantize(sample));
+        let pixels = (0..size.area())
+            .map(|index| {
+                let value = (index * 37 % 256) as u8;
+                match encoding.format.channels() {
+                    _ if encoding.format.is_bitmap() => {
+                        let bit = if index % 3 == 0 { 255 } else { 0 };
+                        RGBA8::new(bit, bit, bit, 255)
+                    }
+                    1 => RGBA8::new(keep(value), keep(value), keep(value), 255),
+                    3 => RGBA8::new(keep(value), keep(!value), keep(value / 2), 255),
+                    _ => RGBA8::new(keep(value), keep(!value), keep(value / 2), keep(value / 3)),
+                }
+            })
+            .collect();
+        Sprite::new(Vec2::new(0.0, 0.0), size, pixels)
+    }
+
+    #[test]
+    fn round_trips() {
+        let path = std::env::temp_dir().join(format!("pixel_editor_{}.ppt", std::process::id()));
+        for format in FORMATS {
+            for maxval in [1, 15, 255, 65535] {
+                let encoding = PptEncoding::new(format, maxval);
+                let sprite = sprite(encoding);
+                save_sprite(&path, &sprite, encoding).unwrap();
+                let (loaded, loaded_encoding) = load_sprite(&path).unwrap();
+                assert_eq!(loaded_encoding, encoding);
+                assert_eq!(loaded.size, sprite.size);
+                assert_eq!(loaded.pixels, sprite.pixels, "{format:?} maxval {maxval}");
+            }
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn pam_extension_in_any_case() {
+        for name in ["a.pam", "A.PAM", "a.Pam"] {
+            let encoding = PptEncoding::for_path(Path::new(name));
+            assert_eq!(encoding.format, PptFormat::P7, "{name}");
+        }
+        let encoding = PptEncoding::for_path(Path::new("a.ppt"));
+        assert_eq!(encoding.format, PptFormat::P3);
+    }
+
+    #[test]
+    fn headers_with_comments_and_whitespace() {
+        let gray = |value| RGBA8::new(value, value, value, 255);
+        let cases: [(&[u8], Vec<RGBA8>); 5] = [
+            (
+                b"P6\n# made by hand\n2 # width\n  1\n#maxval\n255\n\x01\x02\x03\x04\x05\x06",
+                vec![RGBA8::new(1, 2, 3, 255), RGBA8::new(4, 5, 6, 255)],
+            ),
+            (
+                b"P3\t2\r\n1 255 1 2 3#comment\n4\n5\n6",
+                vec![RGBA8::new(1, 2, 3, 255), RGBA8::new(4, 5, 6, 255)],
+            ),
+            (b"P1 3 1\n#bits\n010", vec![gray(255), gray(0), gray(255)]),
+            (b"P2 2 1 15 0 15", vec![gray(0), gray(255)]),
+            (
+                b"P7\n# pam\nWIDTH 1\nHEIGHT 1\nDEPTH 2\nMAXVAL 255\nTUPLTYPE GRAYSCALE_ALPHA\nENDHDR\n\x40\x80",
+                vec![RGBA8::new(64, 64, 64, 128)],
+            ),
+        ];
+        for (data, pixels) in cases {
+            let (sprite, _) = decode_sprite(data).unwrap();
+            assert_eq!(sprite.pixels, pixels, "{}", String::from_utf8_lossy(data));
+        }
+    }
+
+    #[test]
+    fn huge_header_with_little_data_is_truncated() {
+        for data in [
+            &b"P3 100000 100000 255\n1 2 3"[..],
+            b"P3 1000000000 1000000000 255\n1 2 3",
+            b"P1 100000 100000\n0 1",
+        ] {
+            assert!(matches!(
+                decode_sprite(data),
+                Err(ImageError::TruncatedData { found, .. }) if found < 4
+            ));
+        }
+    }
 }
 
 /*
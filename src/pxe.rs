@@ -0,0 +1,555 @@
+//! Project files keeping everything the editor works with, unlike the flat image formats.
+//!
+//! A `.pxe` file starts with the signature `\x89PXE\r\n\x1a\n` and a big endian `u16` version,
+//! followed by chunks until the end of the file. Every chunk is a four letter ASCII type, a big
+//! endian `u32` length and that many bytes of data. Chunks a reader doesn't know are skipped, so
+//! new kinds of data go into new chunks, the version only changes when existing chunks change
+//! in ways older readers can't handle, and readers refuse versions newer than their own.
+//!
+//! All numbers are big endian, colors are `r g b a` bytes.
+//!
+//! - `HEAD`, required and first: `u32` width, `u32` height, `u16` layer count, `u16` frame count.
+//! - `LAYR`, required: for every layer from the bottom up, `u8` flags (bit 0 set when visible),
+//!   `u8` opacity, `u8` blend mode (0 normal, 1 multiply, 2 add, 3 screen, anything else loads
+//!   as normal), `u16` name length and the UTF-8 name.
+//! - `FRAM`, one per frame in order: `u16` duration in milliseconds, then a cel for every layer
+//!   from the bottom up. Cels are width times height pixels in rows from the top left, run
+//!   length encoded as a `u8` count of 1 to 255 followed by the color repeated that many times.
+//! - `TAGS`: `u16` tag count, then for every tag the `u16` first and last frame counted from 0,
+//!   `u16` name length and the UTF-8 name.
+//! - `PLTE`: `u16` color count and the colors.
+//! - `EDIT`: editor state, the selected color, `u16` active layer and `u16` active frame.
+//! - `META`: UTF-8 text of `key=value` lines, e.g. the program that wrote the file.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use simple_pixels::rgb::RGBA8;
+
+use crate::color::BlendMode;
+use crate::error::ImageError;
+use crate::layers::{Layer, LayerStack, Tag, FRAME_DURATION};
+use crate::{Size, Sprite, Vec2};
+
+const SIGNATURE: &[u8; 8] = b"\x89PXE\r\n\x1a\n";
+const VERSION: u16 = 1;
+/// Widest and tallest projects, like GIFs, so that the view and preview sizes can't overflow.
+const SIZE_LIMIT: u32 = 65535;
+
+/// Everything stored in a project file.
+pub struct Project {
+    pub layers: LayerStack,
+    pub palette: Vec<RGBA8>,
+    pub selected_color: RGBA8,
+    /// Free form `key`, `value` pairs kept as they were loaded.
+    pub metadata: Vec<(String, String)>,
+}
+
+pub fn is_project(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pxe"))
+}
+
+fn blend_code(blend: BlendMode) -> u8 {
+    match blend {
+        BlendMode::Normal => 0,
+        BlendMode::Multiply => 1,
+        BlendMode::Add => 2,
+        BlendMode::Screen => 3,
+    }
+}
+
+fn blend_from_code(code: u8) -> BlendMode {
+    match code {
+        1 => BlendMode::Multiply,
+        2 => BlendMode::Add,
+        3 => BlendMode::Screen,
+        _ => BlendMode::Normal,
+    }
+}
+
+fn too_large(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Too many {what} to save in a project"),
+    )
+}
+
+fn push_chunk(data: &mut Vec<u8>, kind: [u8; 4], chunk: &[u8]) -> Result<(), io::Error> {
+    let length = u32::try_from(chunk.len()).map_err(|_| too_large("pixels"))?;
+    data.extend_from_slice(&kind);
+    data.extend_from_slice(&length.to_be_bytes());
+    data.extend_from_slice(chunk);
+    Ok(())
+}
+
+/// Counts, lengths and frame numbers, which have to fit in a `u16`.
+fn push_u16(data: &mut Vec<u8>, value: usize, what: &str) -> Result<(), io::Error> {
+    let value = u16::try_from(value).map_err(|_| too_large(what))?;
+    data.extend_from_slice(&value.to_be_bytes());
+    Ok(())
+}
+
+fn push_color(data: &mut Vec<u8>, color: RGBA8) {
+    data.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+}
+
+fn push_cel(data: &mut Vec<u8>, pixels: &[RGBA8]) {
+    let mut rest = pixels;
+    while let Some(&color) = rest.first() {
+        let run = rest
+            .iter()
+            .take(255)
+            .take_while(|&&pixel| pixel == color)
+            .count();
+        data.push(run as u8);
+        push_color(data, color);
+        rest = &rest[run..];
+    }
+}
+
+/// Reads the numbers of a chunk, running out of data is an error naming the chunk.
+struct Reader<'a> {
+    data: &'a [u8],
+    kind: &'a str,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], ImageError> {
+        if self.data.len() < count {
+            return Err(ImageError::Decode(format!(
+                "{} chunk is too short",
+                self.kind
+            )));
+        }
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ImageError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ImageError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ImageError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn color(&mut self) -> Result<RGBA8, ImageError> {
+        let bytes = self.bytes(4)?;
+        Ok(RGBA8::new(bytes[0], bytes[1], bytes[2], bytes[3]))
+    }
+
+    /// Layers without cels.
+    fn layers(mut self, count: usize) -> Result<Vec<Layer>, ImageError> {
+        let mut layers = Vec::with_capacity(count);
+        for _ in 0..count {
+            let visible = self.u8()? & 1 == 1;
+            let opacity = self.u8()?;
+            let blend = blend_from_code(self.u8()?);
+            let length = usize::from(self.u16()?);
+            let name = String::from_utf8_lossy(self.bytes(length)?).into_owned();
+            layers.push(Layer {
+                name,
+                visible,
+                opacity,
+                blend,
+                cels: Vec::new(),
+            });
+        }
+        Ok(layers)
+    }
+
+    /// Tags outside of the frames can't be shown or exported and are left out.
+    fn tags(mut self, frame_count: usize) -> Result<Vec<Tag>, ImageError> {
+        let mut tags = Vec::new();
+        for _ in 0..self.u16()? {
+            let from = usize::from(self.u16()?);
+            let to = usize::from(self.u16()?);
+            let length = usize::from(self.u16()?);
+            let name = String::from_utf8_lossy(self.bytes(length)?).into_owned();
+            if from <= to && to < frame_count {
+                tags.push(Tag { name, from, to });
+            }
+        }
+        Ok(tags)
+    }
+
+    fn cel(&mut self, size: Size) -> Result<Vec<RGBA8>, ImageError> {
+        // Every run takes five bytes and covers at most 255 pixels.
+        if self.data.len() < size.area().div_ceil(255) * 5 {
+            return Err(ImageError::Decode(format!(
+                "{} chunk is too short",
+                self.kind
+            )));
+        }
+        let mut pixels = Vec::with_capacity(size.area());
+        while pixels.len() < size.area() {
+            let run = usize::from(self.u8()?);
+            let color = self.color()?;
+            if run == 0 || pixels.len() + run > size.area() {
+                return Err(ImageError::Decode(format!(
+                    "Bad run of {run} pixels in a cel"
+                )));
+            }
+            pixels.extend(std::iter::repeat_n(color, run));
+        }
+        Ok(pixels)
+    }
+}
+
+/// Splits the file after the signature and version into chunk types and data.
+fn chunks(mut data: &[u8]) -> Result<Vec<(&str, &[u8])>, ImageError> {
+    let mut chunks = Vec::new();
+    while !data.is_empty() {
+        let mut header = Reader { data, kind: "" };
+        let kind = std::str::from_utf8(header.bytes(4)?)
+            .map_err(|_| ImageError::Decode("Chunk type isn't ASCII".to_string()))?;
+        let length = header.u32()? as usize;
+        let mut reader = Reader {
+            data: header.data,
+            kind,
+        };
+        chunks.push((kind, reader.bytes(length)?));
+        data = reader.data;
+    }
+    Ok(chunks)
+}
+
+impl Project {
+    /// Project of a flat image.
+    pub fn new(canvas: Sprite) -> Self {
+        Self::from_frames(vec![canvas], vec![FRAME_DURATION])
+    }
+
+    /// Project of flat frames, shown for `durations` milliseconds each.
+    pub fn from_frames(frames: Vec<Sprite>, durations: Vec<u16>) -> Self {
+        Self {
+            layers: LayerStack::new(frames, durations),
+            palette: Vec::new(),
+            selected_color: RGBA8::new(100, 100, 100, 255),
+            metadata: Vec::new(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), io::Error> {
+        let layers = &self.layers.layers;
+        let size = self.layers.size();
+        let mut data = SIGNATURE.to_vec();
+        data.extend_from_slice(&VERSION.to_be_bytes());
+
+        if size.width > SIZE_LIMIT || size.height > SIZE_LIMIT {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Projects can't be larger than {SIZE_LIMIT}x{SIZE_LIMIT}"),
+            ));
+        }
+        let mut head = Vec::new();
+        head.extend_from_slice(&size.width.to_be_bytes());
+        head.extend_from_slice(&size.height.to_be_bytes());
+        push_u16(&mut head, layers.len(), "layers")?;
+        push_u16(&mut head, self.layers.frame_count(), "frames")?;
+        push_chunk(&mut data, *b"HEAD", &head)?;
+
+        let mut chunk = Vec::new();
+        for layer in layers {
+            chunk.push(u8::from(layer.visible));
+            chunk.push(layer.opacity);
+            chunk.push(blend_code(layer.blend));
+            push_u16(&mut chunk, layer.name.len(), "bytes in a layer name")?;
+            chunk.extend_from_slice(layer.name.as_bytes());
+        }
+        push_chunk(&mut data, *b"LAYR", &chunk)?;
+
+        for (frame, duration) in self.layers.durations.iter().enumerate() {
+            let mut chunk = duration.to_be_bytes().to_vec();
+            for layer in layers {
+                push_cel(&mut chunk, &layer.cels[frame].pixels);
+            }
+            push_chunk(&mut data, *b"FRAM", &chunk)?;
+        }
+
+        let mut chunk = Vec::new();
+        push_u16(&mut chunk, self.layers.tags.len(), "tags")?;
+        for tag in &self.layers.tags {
+            push_u16(&mut chunk, tag.from, "frames")?;
+            push_u16(&mut chunk, tag.to, "frames")?;
+            push_u16(&mut chunk, tag.name.len(), "bytes in a tag name")?;
+            chunk.extend_from_slice(tag.name.as_bytes());
+        }
+        push_chunk(&mut data, *b"TAGS", &chunk)?;
+
+        let mut chunk = Vec::new();
+        push_u16(&mut chunk, self.palette.len(), "palette colors")?;
+        for &color in &self.palette {
+            push_color(&mut chunk, color);
+        }
+        push_chunk(&mut data, *b"PLTE", &chunk)?;
+
+        let mut chunk = Vec::new();
+        push_color(&mut chunk, self.selected_color);
+        push_u16(&mut chunk, self.layers.active, "layers")?;
+        push_u16(&mut chunk, self.layers.frame, "frames")?;
+        push_chunk(&mut data, *b"EDIT", &chunk)?;
+
+        let mut chunk = Vec::new();
+        for (key, value) in &self.metadata {
+            chunk.extend_from_slice(key.as_bytes());
+            chunk.push(b'=');
+            chunk.extend_from_slice(value.as_bytes());
+            chunk.push(b'\n');
+        }
+        push_chunk(&mut data, *b"META", &chunk)?;
+
+        fs::write(path, data)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ImageError> {
+        let data = fs::read(path)?;
+        let Some(rest) = data.strip_prefix(SIGNATURE) else {
+            return Err(ImageError::BadHeader("Missing pxe signature".to_string()));
+        };
+        let mut reader = Reader {
+            data: rest,
+            kind: "version",
+        };
+        let version = reader.u16()?;
+        if version > VERSION {
+            return Err(ImageError::UnsupportedFormat(format!(
+                "pxe version {version}"
+            )));
+        }
+        let chunks = chunks(reader.data)?;
+        let chunk = |kind: &str| {
+            chunks
+                .iter()
+                .find(|(found, _)| *found == kind)
+                .map(|&(kind, data)| Reader { data, kind })
+        };
+
+        let Some(mut head) = chunks
+            .first()
+            .filter(|(kind, _)| *kind == "HEAD")
+            .map(|&(kind, data)| Reader { data, kind })
+        else {
+            return Err(ImageError::BadHeader("Missing HEAD chunk".to_string()));
+        };
+        let size = Size::new(head.u32()?, head.u32()?);
+        let layer_count = usize::from(head.u16()?);
+        let frame_count = usize::from(head.u16()?);
+        let fits = |length: u32| (1..=SIZE_LIMIT).contains(&length);
+        if !fits(size.width) || !fits(size.height) || layer_count == 0 || frame_count == 0 {
+            return Err(ImageError::BadHeader(format!(
+                "Bad project of {}x{} pixels, {layer_count} layers and {frame_count} frames",
+                size.width, size.height
+            )));
+        }
+
+        let frames: Vec<Reader> = chunks
+            .iter()
+            .filter(|(kind, _)| *kind == "FRAM")
+            .map(|&(kind, data)| Reader { data, kind })
+            .collect();
+        if frames.len() != frame_count {
+            return Err(ImageError::BadHeader(format!(
+                "Expected {frame_count} FRAM chunks, found {}",
+                frames.len()
+            )));
+        }
+        let mut layers = chunk("LAYR")
+            .ok_or_else(|| ImageError::BadHeader("Missing LAYR chunk".to_string()))?
+            .layers(layer_count)?;
+        let mut durations = Vec::with_capacity(frame_count);
+        for mut frame in frames {
+            durations.push(frame.u16()?);
+            for layer in &mut layers {
+                let pixels = frame.cel(size)?;
+                layer
+                    .cels
+                    .push(Sprite::new(Vec2::new(0.0, 0.0), size, pixels));
+            }
+        }
+
+        let tags = match chunk("TAGS") {
+            Some(entries) => entries.tags(frame_count)?,
+            None => Vec::new(),
+        };
+        let palette = match chunk("PLTE") {
+            Some(mut colors) => (0..colors.u16()?)
+                .map(|_| colors.color())
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        let (selected_color, active, frame) = match chunk("EDIT") {
+            Some(mut edit) => (
+                edit.color()?,
+                usize::from(edit.u16()?),
+                usize::from(edit.u16()?),
+            ),
+            None => (RGBA8::new(100, 100, 100, 255), 0, 0),
+        };
+        let metadata = chunk("META").map_or_else(Vec::new, |meta| {
+            String::from_utf8_lossy(meta.data)
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        });
+
+        let mut layers = LayerStack::from_layers(layers, durations, active, frame);
+        layers.tags = tags;
+        Ok(Self {
+            layers,
+            palette,
+            selected_color,
+            metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cel(size: Size, seed: u8) -> Sprite {
+        let pixels = (0..size.area())
+            .map(|index| {
+                RGBA8::new(
+                    seed,
+                    (index / 300) as u8,
+                    7,
+                    if index % 3 == 0 { 0 } else { 255 },
+                )
+            })
+            .collect();
+        Sprite::new(Vec2::new(0.0, 0.0), size, pixels)
+    }
+
+    fn project() -> Project {
+        let size = Size::new(40, 30);
+        let mut project = Project::from_frames(vec![cel(size, 1), cel(size, 2)], vec![100, 250]);
+        let mut top = Layer::new("top ü".to_string(), vec![cel(size, 3), cel(size, 4)]);
+        top.visible = false;
+        top.opacity = 128;
+        top.blend = BlendMode::Screen;
+        project.layers.layers.push(top);
+        project.layers.active = 1;
+        project.layers.frame = 1;
+        project.layers.tags.push(Tag {
+            name: "walk".to_string(),
+            from: 0,
+            to: 1,
+        });
+        project.palette = vec![RGBA8::new(1, 2, 3, 4), RGBA8::new(5, 6, 7, 8)];
+        project.selected_color = RGBA8::new(9, 10, 11, 12);
+        project.metadata = vec![("program".to_string(), "pixel_editor".to_string())];
+        project
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("pixel_editor_{}_{name}.pxe", std::process::id()))
+    }
+
+    fn assert_same(loaded: &Project, saved: &Project) {
+        let (loaded_layers, saved_layers) = (&loaded.layers, &saved.layers);
+        assert_eq!(loaded_layers.durations, saved_layers.durations);
+        assert_eq!(loaded_layers.tags, saved_layers.tags);
+        assert_eq!(loaded_layers.active, saved_layers.active);
+        assert_eq!(loaded_layers.frame, saved_layers.frame);
+        assert_eq!(loaded_layers.layers.len(), saved_layers.layers.len());
+        for (loaded, saved) in loaded_layers.layers.iter().zip(&saved_layers.layers) {
+            assert_eq!(loaded.name, saved.name);
+            assert_eq!(loaded.visible, saved.visible);
+            assert_eq!(loaded.opacity, saved.opacity);
+            assert_eq!(loaded.blend, saved.blend);
+            for (loaded, saved) in loaded.cels.iter().zip(&saved.cels) {
+                assert_eq!(loaded.size, saved.size);
+                assert_eq!(loaded.pixels, saved.pixels);
+            }
+        }
+        assert_eq!(loaded.palette, saved.palette);
+        assert_eq!(loaded.selected_color, saved.selected_color);
+        assert_eq!(loaded.metadata, saved.metadata);
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round_trip");
+        let project = project();
+        project.save(&path).unwrap();
+        let loaded = Project::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_same(&loaded.unwrap(), &project);
+    }
+
+    #[test]
+    fn unknown_chunks_are_skipped() {
+        let path = temp_path("unknown_chunks");
+        let project = project();
+        project.save(&path).unwrap();
+        let mut data = fs::read(&path).unwrap();
+        // After the signature, the version and the HEAD chunk.
+        let head_end = SIGNATURE.len() + 2 + 8 + 12;
+        let mut unknown = Vec::new();
+        push_chunk(&mut unknown, *b"XTRA", b"from a newer editor").unwrap();
+        data.splice(head_end..head_end, unknown.iter().copied());
+        push_chunk(&mut data, *b"zzzz", &[]).unwrap();
+        fs::write(&path, data).unwrap();
+        let loaded = Project::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_same(&loaded.unwrap(), &project);
+    }
+
+    /// Project of one layer and frame with a single run of pixels, whatever the HEAD says.
+    fn load_head(width: u32, height: u32) -> Result<Project, ImageError> {
+        let path = temp_path(&format!("head_{width}x{height}"));
+        let mut data = SIGNATURE.to_vec();
+        data.extend_from_slice(&VERSION.to_be_bytes());
+        let mut head = Vec::new();
+        head.extend_from_slice(&width.to_be_bytes());
+        head.extend_from_slice(&height.to_be_bytes());
+        head.extend_from_slice(&[0, 1, 0, 1]);
+        push_chunk(&mut data, *b"HEAD", &head).unwrap();
+        push_chunk(&mut data, *b"LAYR", &[1, 255, 0, 0, 0]).unwrap();
+        push_chunk(&mut data, *b"FRAM", &[0, 100, 255, 0, 0, 0, 255]).unwrap();
+        fs::write(&path, data).unwrap();
+        let loaded = Project::load(&path);
+        fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn oversized_head_is_rejected() {
+        for (width, height) in [(70000, 70000), (500_000_000, 1), (1, 500_000_000), (0, 5)] {
+            assert!(
+                matches!(load_head(width, height), Err(ImageError::BadHeader(_))),
+                "{width}x{height}"
+            );
+        }
+        // Sizes within the limit still need the pixels.
+        assert!(matches!(
+            load_head(65535, 65535),
+            Err(ImageError::Decode(_))
+        ));
+        assert!(load_head(15, 17).is_ok());
+    }
+
+    #[test]
+    fn too_many_colors_are_an_error() {
+        let path = temp_path("too_many_colors");
+        let mut project = project();
+        project.palette = vec![RGBA8::default(); usize::from(u16::MAX) + 1];
+        let error = project.save(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,193 @@
+/// Cells of a Bresenham line from `from` to `to`, both ends included.
+pub fn line(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let step_x = if x < to.0 { 1 } else { -1 };
+    let step_y = if y < to.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut cells = Vec::with_capacity((dx - dy + 1) as usize);
+    loop {
+        cells.push((x, y));
+        if (x, y) == to {
+            return cells;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Cells of an axis aligned rectangle with opposite corners `from` and `to`.
+pub fn rectangle(from: (i32, i32), to: (i32, i32), filled: bool) -> Vec<(i32, i32)> {
+    let (left, right) = (from.0.min(to.0), from.0.max(to.0));
+    let (top, bottom) = (from.1.min(to.1), from.1.max(to.1));
+    let mut cells = Vec::new();
+    for y in top..=bottom {
+        for x in left..=right {
+            if filled || x == left || x == right || y == top || y == bottom {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+/// Cells of the ellipse inscribed in the rectangle with opposite corners `from` and `to`.
+///
+/// Uses Alois Zingl's bresenham variant, which also handles even sized boxes.
+pub fn ellipse(from: (i32, i32), to: (i32, i32), filled: bool) -> Vec<(i32, i32)> {
+    let (mut x0, mut x1) = (i64::from(from.0.min(to.0)), i64::from(from.0.max(to.0)));
+    let (top, bottom) = (i64::from(from.1.min(to.1)), i64::from(from.1.max(to.1)));
+    let a = x1 - x0;
+    let b = bottom - top;
+    let b1 = b & 1;
+    let mut dx = 4 * (1 - a) * b * b;
+    let mut dy = 4 * (b1 + 1) * a * a;
+    let mut error = dx + dy + b1 * a * a;
+    let mut y0 = top + (b + 1) / 2;
+    let mut y1 = y0 - b1;
+    let a8 = 8 * a * a;
+    let b8 = 8 * b * b;
+
+    let mut outline: Vec<(i64, i64)> = Vec::new();
+    loop {
+        outline.extend_from_slice(&[(x1, y0), (x0, y0), (x0, y1), (x1, y1)]);
+        let doubled = 2 * error;
+        if doubled <= dy {
+            y0 += 1;
+            y1 -= 1;
+            dy += a8;
+            error += dy;
+        }
+        if doubled >= dx || 2 * error > dy {
+            x0 += 1;
+            x1 -= 1;
+            dx += b8;
+            error += dx;
+        }
+        if x0 > x1 {
+            break;
+        }
+    }
+    // Flat ellipses stop too early, finish their tips up to the first and last rows.
+    while y0 - y1 <= b {
+        outline.extend_from_slice(&[(x0 - 1, y0), (x1 + 1, y0), (x0 - 1, y1), (x1 + 1, y1)]);
+        y0 += 1;
+        y1 -= 1;
+    }
+
+    let outline = outline.into_iter().map(|(x, y)| (x as i32, y as i32));
+    if !filled {
+        return outline.collect();
+    }
+    // Span every row between its leftmost and rightmost outline cell.
+    let mut spans: Vec<(i32, i32, i32)> = Vec::new();
+    for (x, y) in outline {
+        match spans.iter_mut().find(|(row, _, _)| *row == y) {
+            Some((_, left, right)) => {
+                *left = (*left).min(x);
+                *right = (*right).max(x);
+            }
+            None => spans.push((y, x, x)),
+        }
+    }
+    spans
+        .into_iter()
+        .flat_map(|(y, left, right)| (left..=right).map(move |x| (x, y)))
+        .collect()
+}
+
+/// Snaps `to` so that the line from `from` is horizontal, vertical or diagonal.
+pub fn snap_45(from: (i32, i32), to: (i32, i32)) -> (i32, i32) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    if dx.abs() > 2 * dy.abs() {
+        (to.0, from.1)
+    } else if dy.abs() > 2 * dx.abs() {
+        (from.0, to.1)
+    } else {
+        snap_square(from, to)
+    }
+}
+
+/// Snaps `to` so that `from` and `to` are opposite corners of a square.
+pub fn snap_square(from: (i32, i32), to: (i32, i32)) -> (i32, i32) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let side = dx.abs().max(dy.abs());
+    let sign = |delta: i32| if delta < 0 { -1 } else { 1 };
+    (from.0 + sign(dx) * side, from.1 + sign(dy) * side)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Outline and filled ellipses of a box `width` by `height` cells stay inside of it, touch
+    /// all four sides, reach every row and column and are symmetric.
+    fn check_ellipse(width: i32, height: i32) {
+        for filled in [false, true] {
+            let (from, to) = ((3, 5), (3 + width - 1, 5 + height - 1));
+            let cells: HashSet<(i32, i32)> = ellipse(from, to, filled).into_iter().collect();
+            let name = format!("{width}x{height} filled {filled}");
+            for &(x, y) in &cells {
+                assert!(
+                    (3..3 + width).contains(&x) && (5..5 + height).contains(&y),
+                    "{name}"
+                );
+                assert!(cells.contains(&(to.0 + from.0 - x, y)), "{name}");
+                assert!(cells.contains(&(x, to.1 + from.1 - y)), "{name}");
+            }
+            for y in 5..5 + height {
+                assert!(cells.iter().any(|cell| cell.1 == y), "{name} row {y}");
+            }
+            for x in 3..3 + width {
+                assert!(cells.iter().any(|cell| cell.0 == x), "{name} column {x}");
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_ellipses() {
+        let dot: HashSet<_> = ellipse((2, 2), (2, 2), true).into_iter().collect();
+        assert_eq!(dot, HashSet::from([(2, 2)]));
+        let column: HashSet<_> = ellipse((4, 1), (4, 6), false).into_iter().collect();
+        assert_eq!(column, (1..=6).map(|y| (4, y)).collect());
+        let row: HashSet<_> = ellipse((1, 4), (6, 4), true).into_iter().collect();
+        assert_eq!(row, (1..=6).map(|x| (x, 4)).collect());
+    }
+
+    #[test]
+    fn ellipses_reach_the_edges_of_their_box() {
+        for (width, height) in [
+            (1, 3),
+            (1, 6),
+            (2, 6),
+            (3, 8),
+            (4, 13),
+            (3, 1),
+            (8, 3),
+            (2, 2),
+            (5, 5),
+            (10, 10),
+            (16, 7),
+        ] {
+            check_ellipse(width, height);
+        }
+    }
+
+    #[test]
+    fn rectangles_and_lines() {
+        assert_eq!(rectangle((2, 2), (0, 0), false).len(), 8);
+        assert_eq!(rectangle((0, 0), (2, 2), true).len(), 9);
+        assert_eq!(line((0, 0), (3, 1)), vec![(0, 0), (1, 0), (2, 1), (3, 1)]);
+        assert_eq!(snap_45((0, 0), (5, 1)), (5, 0));
+        assert_eq!(snap_square((0, 0), (-2, 5)), (-5, 5));
+    }
+}
@@ -0,0 +1,539 @@
+//! Sprite sheets of every frame for game engines, with the frame rectangles, durations and tags
+//! in a JSON file like the array format Aseprite exports, and slicing existing sheets back into
+//! frames.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use simple_pixels::rgb::RGBA8;
+
+use crate::image::{save_image, ImageFormat};
+use crate::layers::Tag;
+use crate::{Size, Sprite, Vec2};
+
+/// How frames are arranged on the sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SheetLayout {
+    /// Whole frames in rows of equal cells.
+    #[default]
+    Grid,
+    /// Frames trimmed to their opaque pixels and packed in shelves, tallest first.
+    Packed,
+}
+
+impl FromStr for SheetLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grid" => Ok(Self::Grid),
+            "packed" => Ok(Self::Packed),
+            _ => Err(format!("Unknown sheet layout {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SheetOptions {
+    pub layout: SheetLayout,
+    /// Columns of the grid, defaults to a roughly square sheet.
+    pub columns: Option<u32>,
+    /// Transparent pixels between frames.
+    pub padding: u32,
+    /// Pixels the edges of every frame are repeated outwards, so that filtering at the edges
+    /// doesn't pick up the neighbours.
+    pub extrude: u32,
+}
+
+/// Grid of cells to cut a sheet into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SliceOptions {
+    pub cell: Size,
+    /// Pixels around the cells at the edges of the sheet.
+    pub margin: u32,
+    /// Pixels between neighbouring cells.
+    pub spacing: u32,
+    /// Leaves out cells without a single pixel that isn't fully transparent.
+    pub skip_empty: bool,
+}
+
+/// Part of a frame, in frame pixels.
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Smallest rectangle around the pixels that aren't fully transparent, the top left pixel for
+/// empty frames.
+fn trim(frame: &Sprite) -> Rect {
+    let width = frame.size.width;
+    let opaque = frame
+        .pixels
+        .iter()
+        .enumerate()
+        .filter(|(_, pixel)| pixel.a != 0)
+        .map(|(index, _)| (index as u32 % width, index as u32 / width));
+    let bounds = opaque.fold(None, |bounds: Option<(u32, u32, u32, u32)>, (x, y)| {
+        Some(bounds.map_or((x, y, x, y), |(left, top, right, bottom)| {
+            (left.min(x), top.min(y), right.max(x), bottom.max(y))
+        }))
+    });
+    let (left, top, right, bottom) = bounds.unwrap_or_default();
+    Rect {
+        x: left,
+        y: top,
+        width: right - left + 1,
+        height: bottom - top + 1,
+    }
+}
+
+/// Position of every frame's kept part on the sheet and the size of the sheet.
+fn grid(count: usize, frame: Size, options: SheetOptions) -> (Vec<(u32, u32)>, Size) {
+    let count = count as u32;
+    let columns = options
+        .columns
+        .unwrap_or_else(|| f64::from(count).sqrt().ceil() as u32)
+        .clamp(1, count);
+    let rows = count.div_ceil(columns);
+    let cell = Size::new(
+        frame.width + 2 * options.extrude,
+        frame.height + 2 * options.extrude,
+    );
+    let positions = (0..count)
+        .map(|index| {
+            let (column, row) = (index % columns, index / columns);
+            (
+                column * (cell.width + options.padding) + options.extrude,
+                row * (cell.height + options.padding) + options.extrude,
+            )
+        })
+        .collect();
+    let size = Size::new(
+        columns * (cell.width + options.padding) - options.padding,
+        rows * (cell.height + options.padding) - options.padding,
+    );
+    (positions, size)
+}
+
+/// Shelves about as wide as a square sheet would be, filled left to right with the tallest
+/// frames first.
+fn pack(rects: &[Rect], options: SheetOptions) -> (Vec<(u32, u32)>, Size) {
+    let border = 2 * options.extrude;
+    let cell = |rect: &Rect| (rect.width + border, rect.height + border);
+    let area: u32 = rects
+        .iter()
+        .map(|rect| {
+            let (width, height) = cell(rect);
+            (width + options.padding) * (height + options.padding)
+        })
+        .sum();
+    let widest = rects.iter().map(|rect| cell(rect).0).max().unwrap_or(0);
+    let shelf_width = widest.max(f64::from(area).sqrt().ceil() as u32);
+
+    let mut order: Vec<usize> = (0..rects.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(rects[index].height));
+    let mut positions = vec![(0, 0); rects.len()];
+    let (mut x, mut y, mut shelf_height, mut sheet_width) = (0, 0, 0, 0);
+    for index in order {
+        let (width, height) = cell(&rects[index]);
+        if x > 0 && x + width > shelf_width {
+            y += shelf_height + options.padding;
+            (x, shelf_height) = (0, 0);
+        }
+        positions[index] = (x + options.extrude, y + options.extrude);
+        sheet_width = sheet_width.max(x + width);
+        shelf_height = shelf_height.max(height);
+        x += width + options.padding;
+    }
+    (positions, Size::new(sheet_width, y + shelf_height))
+}
+
+fn json_string(text: &str) -> String {
+    let escaped: String = text
+        .chars()
+        .map(|ch| match ch {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            ch if u32::from(ch) < 0x20 => format!("\\u{:04x}", u32::from(ch)),
+            ch => ch.to_string(),
+        })
+        .collect();
+    format!("\"{escaped}\"")
+}
+
+/// Saves the frames to `path` in the image format of its extension and the metadata next to it,
+/// `walk.png` gets `walk.json`. Frames are named `NAME INDEX` after the edited file.
+pub fn export_sheet(
+    path: &PathBuf,
+    name: &str,
+    frames: &[Sprite],
+    durations: &[u16],
+    tags: &[Tag],
+    options: SheetOptions,
+) -> Result<(), io::Error> {
+    let frame_size = frames[0].size;
+    let rects: Vec<Rect> = match options.layout {
+        SheetLayout::Grid => vec![
+            Rect {
+                x: 0,
+                y: 0,
+                width: frame_size.width,
+                height: frame_size.height,
+            };
+            frames.len()
+        ],
+        SheetLayout::Packed => frames.iter().map(trim).collect(),
+    };
+    let (positions, size) = match options.layout {
+        SheetLayout::Grid => grid(frames.len(), frame_size, options),
+        SheetLayout::Packed => pack(&rects, options),
+    };
+
+    let mut pixels = vec![RGBA8::default(); size.area()];
+    let extrude = options.extrude as i32;
+    for ((frame, rect), &(x, y)) in frames.iter().zip(&rects).zip(&positions) {
+        for dy in -extrude..rect.height as i32 + extrude {
+            for dx in -extrude..rect.width as i32 + extrude {
+                // Extruded pixels repeat the nearest edge.
+                let source_x = rect.x + dx.clamp(0, rect.width as i32 - 1) as u32;
+                let source_y = rect.y + dy.clamp(0, rect.height as i32 - 1) as u32;
+                let pixel = frame.pixels[(source_y * frame_size.width + source_x) as usize];
+                let (sheet_x, sheet_y) = ((x as i32 + dx) as u32, (y as i32 + dy) as u32);
+                pixels[(sheet_y * size.width + sheet_x) as usize] = pixel;
+            }
+        }
+    }
+    let sheet = Sprite::new(Vec2::new(0.0, 0.0), size, pixels);
+    save_image(path, &sheet, ImageFormat::for_path(path))?;
+
+    let trimmed = options.layout == SheetLayout::Packed;
+    let entries: Vec<String> = rects
+        .iter()
+        .zip(&positions)
+        .zip(durations)
+        .enumerate()
+        .map(|(index, ((rect, (x, y)), duration))| {
+            format!(
+                concat!(
+                    "  {{ \"filename\": {}, \"frame\": {{ \"x\": {}, \"y\": {}, \"w\": {}, ",
+                    "\"h\": {} }}, \"rotated\": false, \"trimmed\": {}, \"spriteSourceSize\": ",
+                    "{{ \"x\": {}, \"y\": {}, \"w\": {}, \"h\": {} }}, \"sourceSize\": ",
+                    "{{ \"w\": {}, \"h\": {} }}, \"duration\": {} }}"
+                ),
+                json_string(&format!("{name} {index}")),
+                x,
+                y,
+                rect.width,
+                rect.height,
+                trimmed,
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height,
+                frame_size.width,
+                frame_size.height,
+                duration
+            )
+        })
+        .collect();
+    let tags: Vec<String> = tags
+        .iter()
+        .map(|tag| {
+            format!(
+                "   {{ \"name\": {}, \"from\": {}, \"to\": {}, \"direction\": \"forward\" }}",
+                json_string(&tag.name),
+                tag.from,
+                tag.to
+            )
+        })
+        .collect();
+    let image = path
+        .file_name()
+        .map_or_else(String::new, |file| file.to_string_lossy().into_owned());
+    let json = format!(
+        concat!(
+            "{{ \"frames\": [\n{}\n ],\n \"meta\": {{\n  \"app\": \"pixel_editor\",\n",
+            "  \"version\": \"{}\",\n  \"image\": {},\n  \"format\": \"RGBA8888\",\n",
+            "  \"size\": {{ \"w\": {}, \"h\": {} }},\n  \"scale\": \"1\",\n",
+            "  \"frameTags\": [{}]\n }}\n}}\n"
+        ),
+        entries.join(",\n"),
+        env!("CARGO_PKG_VERSION"),
+        json_string(&image),
+        size.width,
+        size.height,
+        if tags.is_empty() {
+            String::new()
+        } else {
+            format!("\n{}\n  ", tags.join(",\n"))
+        }
+    );
+    fs::write(path.with_extension("json"), json)
+}
+
+/// Cells of the sheet row by row, the partial cells at the right and bottom edges are left out.
+pub fn slice_sheet(sheet: &Sprite, options: SliceOptions) -> Vec<Sprite> {
+    let SliceOptions {
+        cell,
+        margin,
+        spacing,
+        skip_empty,
+    } = options;
+    let fit =
+        |length: u32, cell: u32| (length.saturating_sub(2 * margin) + spacing) / (cell + spacing);
+    let columns = fit(sheet.size.width, cell.width);
+    let rows = fit(sheet.size.height, cell.height);
+    let mut frames = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let left = margin + column * (cell.width + spacing);
+            let top = margin + row * (cell.height + spacing);
+            let pixels: Vec<RGBA8> = (top..top + cell.height)
+                .flat_map(|y| {
+                    let start = (y * sheet.size.width + left) as usize;
+                    sheet.pixels[start..start + cell.width as usize]
+                        .iter()
+                        .copied()
+                })
+                .collect();
+            if skip_empty && pixels.iter().all(|pixel| pixel.a == 0) {
+                continue;
+            }
+            frames.push(Sprite::new(Vec2::new(0.0, 0.0), cell, pixels));
+        }
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::load_image;
+
+    fn frame(size: Size, seed: u8) -> Sprite {
+        let pixels = (0..size.area())
+            .map(|index| RGBA8::new(seed, index as u8, 0, 255))
+            .collect();
+        Sprite::new(Vec2::new(0.0, 0.0), size, pixels)
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("pixel_editor_{}_{name}", std::process::id()))
+    }
+
+    #[test]
+    fn trim_to_opaque_pixels() {
+        let mut sprite = frame(Size::new(5, 4), 1);
+        sprite.pixels.fill(RGBA8::default());
+        let rect = trim(&sprite);
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (0, 0, 1, 1));
+        sprite.pixels[5 + 1] = RGBA8::new(1, 1, 1, 1);
+        sprite.pixels[2 * 5 + 3] = RGBA8::new(1, 1, 1, 255);
+        let rect = trim(&sprite);
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (1, 1, 3, 2));
+    }
+
+    #[test]
+    fn grid_layout() {
+        let options = SheetOptions {
+            padding: 1,
+            extrude: 1,
+            ..SheetOptions::default()
+        };
+        let (positions, size) = grid(5, Size::new(4, 3), options);
+        assert_eq!(positions, [(1, 1), (8, 1), (15, 1), (1, 7), (8, 7)]);
+        assert_eq!(size, Size::new(20, 11));
+        let options = SheetOptions {
+            columns: Some(9),
+            ..SheetOptions::default()
+        };
+        let (positions, size) = grid(2, Size::new(4, 3), options);
+        assert_eq!(positions, [(0, 0), (4, 0)]);
+        assert_eq!(size, Size::new(8, 3));
+    }
+
+    #[test]
+    fn packed_frames_dont_overlap() {
+        let rects: Vec<Rect> = [(3, 7), (10, 2), (4, 4), (1, 1), (6, 5), (2, 9)]
+            .into_iter()
+            .map(|(width, height)| Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            })
+            .collect();
+        let options = SheetOptions {
+            layout: SheetLayout::Packed,
+            padding: 2,
+            extrude: 1,
+            ..SheetOptions::default()
+        };
+        let (positions, size) = pack(&rects, options);
+        // Extruded cells with the padding after them.
+        let cells: Vec<(u32, u32, u32, u32)> = rects
+            .iter()
+            .zip(&positions)
+            .map(|(rect, &(x, y))| (x - 1, y - 1, rect.width + 2, rect.height + 2))
+            .collect();
+        for (index, &(x, y, width, height)) in cells.iter().enumerate() {
+            assert!(x + width <= size.width && y + height <= size.height);
+            for &(other_x, other_y, other_width, other_height) in &cells[index + 1..] {
+                let apart_x = x + width + 2 <= other_x || other_x + other_width + 2 <= x;
+                let apart_y = y + height + 2 <= other_y || other_y + other_height + 2 <= y;
+                assert!(apart_x || apart_y, "{cells:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn export_grid_sheet_and_json() {
+        let path = temp_path("sheet.png");
+        let size = Size::new(3, 2);
+        let frames = [frame(size, 10), frame(size, 20)];
+        let tags = [Tag {
+            name: "walk \"fast\"".to_string(),
+            from: 0,
+            to: 1,
+        }];
+        let options = SheetOptions {
+            padding: 1,
+            extrude: 1,
+            ..SheetOptions::default()
+        };
+        export_sheet(&path, "hero", &frames, &[100, 250], &tags, options).unwrap();
+        let json = fs::read_to_string(path.with_extension("json")).unwrap();
+        let (sheets, _, _) = load_image(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("json")).unwrap();
+
+        let sheet = &sheets[0];
+        assert_eq!(sheet.size, Size::new(11, 4));
+        let pixel = |x: u32, y: u32| sheet.pixels[(y * sheet.size.width + x) as usize];
+        // Frames sit inside of their extruded edges, which repeat the corners.
+        assert_eq!(pixel(1, 1), frames[0].pixels[0]);
+        assert_eq!(pixel(0, 0), frames[0].pixels[0]);
+        assert_eq!(pixel(10, 3), frames[1].pixels[5]);
+        assert_eq!(pixel(5, 0), RGBA8::default());
+
+        assert!(json.contains(concat!(
+            "{ \"filename\": \"hero 1\", \"frame\": { \"x\": 7, \"y\": 1, \"w\": 3, \"h\": 2 }, ",
+            "\"rotated\": false, \"trimmed\": false, \"spriteSourceSize\": ",
+            "{ \"x\": 0, \"y\": 0, \"w\": 3, \"h\": 2 }, \"sourceSize\": { \"w\": 3, \"h\": 2 }, ",
+            "\"duration\": 250 }"
+        )));
+        assert!(json.contains(
+            "{ \"name\": \"walk \\\"fast\\\"\", \"from\": 0, \"to\": 1, \"direction\": \"forward\" }"
+        ));
+        assert!(json.contains("\"image\": \"pixel_editor_"));
+        assert!(json.contains("\"size\": { \"w\": 11, \"h\": 4 }"));
+    }
+
+    #[test]
+    fn export_packed_sheet_without_tags() {
+        let path = temp_path("packed.png");
+        let mut sprite = frame(Size::new(6, 6), 5);
+        for (index, pixel) in sprite.pixels.iter_mut().enumerate() {
+            if index % 6 < 2 || index / 6 < 3 {
+                pixel.a = 0;
+            }
+        }
+        let options = SheetOptions {
+            layout: SheetLayout::Packed,
+            ..SheetOptions::default()
+        };
+        export_sheet(&path, "box", &[sprite], &[100], &[], options).unwrap();
+        let json = fs::read_to_string(path.with_extension("json")).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("json")).unwrap();
+        assert!(json.contains("\"frame\": { \"x\": 0, \"y\": 0, \"w\": 4, \"h\": 3 }"));
+        assert!(json.contains("\"trimmed\": true"));
+        assert!(json.contains("\"spriteSourceSize\": { \"x\": 2, \"y\": 3, \"w\": 4, \"h\": 3 }"));
+        assert!(json.contains("\"frameTags\": []"));
+    }
+
+    /// Sheet whose pixels are their own coordinates.
+    fn coordinates(size: Size) -> Sprite {
+        let pixels = (0..size.area())
+            .map(|index| {
+                let (x, y) = (index as u32 % size.width, index as u32 / size.width);
+                RGBA8::new(x as u8, y as u8, 0, 255)
+            })
+            .collect();
+        Sprite::new(Vec2::new(0.0, 0.0), size, pixels)
+    }
+
+    fn origins(frames: &[Sprite]) -> Vec<(u8, u8)> {
+        frames
+            .iter()
+            .map(|frame| (frame.pixels[0].r, frame.pixels[0].g))
+            .collect()
+    }
+
+    #[test]
+    fn slice_with_margin_and_spacing() {
+        let options = SliceOptions {
+            cell: Size::new(3, 2),
+            margin: 1,
+            spacing: 2,
+            skip_empty: false,
+        };
+        let frames = slice_sheet(&coordinates(Size::new(14, 10)), options);
+        assert_eq!(origins(&frames), [(1, 1), (6, 1), (1, 5), (6, 5)]);
+        let last = &frames[3];
+        assert_eq!(last.size, Size::new(3, 2));
+        assert_eq!(last.pixels[5], RGBA8::new(8, 6, 0, 255));
+    }
+
+    #[test]
+    fn partial_cells_are_left_out() {
+        let options = SliceOptions {
+            cell: Size::new(4, 4),
+            margin: 0,
+            spacing: 0,
+            skip_empty: false,
+        };
+        let frames = slice_sheet(&coordinates(Size::new(11, 9)), options);
+        assert_eq!(origins(&frames), [(0, 0), (4, 0), (0, 4), (4, 4)]);
+        assert!(slice_sheet(&coordinates(Size::new(3, 9)), options).is_empty());
+        // Margins are on every side, cells running into the far ones are partial too.
+        let options = SliceOptions {
+            margin: 1,
+            ..options
+        };
+        let frames = slice_sheet(&coordinates(Size::new(10, 6)), options);
+        assert_eq!(origins(&frames), [(1, 1), (5, 1)]);
+        assert!(slice_sheet(&coordinates(Size::new(10, 5)), options).is_empty());
+    }
+
+    #[test]
+    fn skip_empty_cells() {
+        let mut sheet = coordinates(Size::new(6, 4));
+        // Clears the second cell of the first row, and all but one pixel of the first one.
+        for y in 0..2 {
+            for x in 0..6 {
+                if (x, y) != (1, 1) {
+                    sheet.pixels[y * 6 + x].a = 0;
+                }
+            }
+        }
+        let options = SliceOptions {
+            cell: Size::new(3, 2),
+            margin: 0,
+            spacing: 0,
+            skip_empty: true,
+        };
+        let frames = slice_sheet(&sheet, options);
+        assert_eq!(origins(&frames), [(0, 0), (0, 2), (3, 2)]);
+        let options = SliceOptions {
+            skip_empty: false,
+            ..options
+        };
+        assert_eq!(slice_sheet(&sheet, options).len(), 4);
+    }
+}
@@ -1,14 +1,69 @@
 use std::cmp::{max, min};
+use std::str::FromStr;
 
 use crate::common::{Size, Vec2};
 use simple_pixels::{rgb::RGBA8, Context};
 
+#[derive(Clone)]
 pub struct Sprite {
     pub origin: Vec2,
     pub size: Size,
     pub pixels: Vec<RGBA8>,
 }
 
+/// Which part of a sprite stays in place when it's resized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Where the old sprite ends up inside the resized one.
+    fn offset(self, from: Size, to: Size) -> (i32, i32) {
+        let dx = to.width as i32 - from.width as i32;
+        let dy = to.height as i32 - from.height as i32;
+        let x = match self {
+            Self::TopLeft | Self::Left | Self::BottomLeft => 0,
+            Self::Top | Self::Center | Self::Bottom => dx / 2,
+            Self::TopRight | Self::Right | Self::BottomRight => dx,
+        };
+        let y = match self {
+            Self::TopLeft | Self::Top | Self::TopRight => 0,
+            Self::Left | Self::Center | Self::Right => dy / 2,
+            Self::BottomLeft | Self::Bottom | Self::BottomRight => dy,
+        };
+        (x, y)
+    }
+}
+
+impl FromStr for Anchor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top-left" => Ok(Self::TopLeft),
+            "top" => Ok(Self::Top),
+            "top-right" => Ok(Self::TopRight),
+            "left" => Ok(Self::Left),
+            "center" => Ok(Self::Center),
+            "right" => Ok(Self::Right),
+            "bottom-left" => Ok(Self::BottomLeft),
+            "bottom" => Ok(Self::Bottom),
+            "bottom-right" => Ok(Self::BottomRight),
+            _ => Err(format!("Unknown anchor {s}")),
+        }
+    }
+}
+
 impl Sprite {
     pub fn new(pos: Vec2, size: Size, pixels: Vec<RGBA8>) -> Self {
         Self {
@@ -17,6 +72,40 @@ impl Sprite {
             pixels,
         }
     }
+    /// Crops or pads the sprite with transparent pixels, keeping the anchor in place.
+    pub fn resized(&self, size: Size, anchor: Anchor) -> Self {
+        let (offset_x, offset_y) = anchor.offset(self.size, size);
+        let mut pixels = vec![RGBA8::default(); size.area()];
+        for y in 0..size.height as i32 {
+            let from_y = y - offset_y;
+            if from_y < 0 || from_y >= self.size.height as i32 {
+                continue;
+            }
+            for x in 0..size.width as i32 {
+                let from_x = x - offset_x;
+                if from_x < 0 || from_x >= self.size.width as i32 {
+                    continue;
+                }
+                pixels[(y * size.width as i32 + x) as usize] =
+                    self.pixels[(from_y * self.size.width as i32 + from_x) as usize];
+            }
+        }
+        Self::new(Vec2::new(self.origin.x, self.origin.y), size, pixels)
+    }
+
+    /// Nearest neighbour upscale by a whole factor.
+    pub fn scaled(&self, factor: u32) -> Self {
+        let size = Size::new(self.size.width * factor, self.size.height * factor);
+        let pixels = (0..size.height)
+            .flat_map(|y| {
+                (0..size.width).map(move |x| {
+                    self.pixels[((y / factor) * self.size.width + x / factor) as usize]
+                })
+            })
+            .collect();
+        Self::new(Vec2::new(self.origin.x, self.origin.y), size, pixels)
+    }
+
     pub fn draw(&self, ctx: &mut Context) {
         let screen_width = ctx.width();
         let screen_height = ctx.height();
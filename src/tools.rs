@@ -0,0 +1,64 @@
+use crate::shapes;
+
+/// What the mouse does on the canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Pen,
+    Line,
+    Rectangle { filled: bool },
+    Ellipse { filled: bool },
+    Fill,
+}
+
+impl Tool {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pen => "pen",
+            Self::Line => "line",
+            Self::Rectangle { filled: false } => "rectangle",
+            Self::Rectangle { filled: true } => "filled rectangle",
+            Self::Ellipse { filled: false } => "ellipse",
+            Self::Ellipse { filled: true } => "filled ellipse",
+            Self::Fill => "fill",
+        }
+    }
+
+    /// Cells covered by dragging a shape tool from `from` to `to`, `None` for the other tools.
+    ///
+    /// Constraining makes lines snap to 45 degrees and the other shapes square.
+    pub fn shape(
+        self,
+        from: (i32, i32),
+        to: (i32, i32),
+        constrain: bool,
+    ) -> Option<Vec<(i32, i32)>> {
+        let cells = match self {
+            Self::Pen | Self::Fill => return None,
+            Self::Line => {
+                let to = if constrain {
+                    shapes::snap_45(from, to)
+                } else {
+                    to
+                };
+                shapes::line(from, to)
+            }
+            Self::Rectangle { filled } => {
+                let to = if constrain {
+                    shapes::snap_square(from, to)
+                } else {
+                    to
+                };
+                shapes::rectangle(from, to, filled)
+            }
+            Self::Ellipse { filled } => {
+                let to = if constrain {
+                    shapes::snap_square(from, to)
+                } else {
+                    to
+                };
+                shapes::ellipse(from, to, filled)
+            }
+        };
+        Some(cells)
+    }
+}
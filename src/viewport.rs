@@ -0,0 +1,107 @@
+//! Zoom and pan of the canvas inside the window.
+
+use std::ops::Range;
+
+use crate::Size;
+
+/// Zoom levels stepped through by the zoom keys.
+const ZOOM_LEVELS: [u32; 12] = [1, 2, 3, 4, 6, 8, 12, 16, 24, 32, 48, 64];
+
+pub struct Viewport {
+    /// Screen pixels per canvas pixel.
+    pub zoom: u32,
+    /// Screen position of the top left corner of the canvas, negative when panned past it.
+    pub offset: (i32, i32),
+    /// Part of the window showing the canvas.
+    pub area: Size,
+}
+
+impl Viewport {
+    /// Centers the canvas in the area.
+    pub fn new(zoom: u32, canvas: Size, area: Size) -> Self {
+        let mut viewport = Self {
+            zoom: zoom.max(1),
+            offset: (0, 0),
+            area,
+        };
+        viewport.center(canvas);
+        viewport
+    }
+
+    pub fn center(&mut self, canvas: Size) {
+        let center = |area: u32, canvas: u32| (area as i32 - (canvas * self.zoom) as i32) / 2;
+        self.offset = (
+            center(self.area.width, canvas.width),
+            center(self.area.height, canvas.height),
+        );
+    }
+
+    /// Largest zoom showing the whole canvas, centered.
+    pub fn fit(&mut self, canvas: Size) {
+        let zoom = (self.area.width / canvas.width).min(self.area.height / canvas.height);
+        self.zoom = zoom.max(1);
+        self.center(canvas);
+    }
+
+    /// Canvas cell under a screen position, may be outside of the canvas.
+    pub fn screen_to_cell(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        let zoom = self.zoom as i32;
+        (
+            (x - self.offset.0).div_euclid(zoom),
+            (y - self.offset.1).div_euclid(zoom),
+        )
+    }
+
+    /// Screen position of the top left corner of a cell.
+    pub fn cell_to_screen(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        let zoom = self.zoom as i32;
+        (self.offset.0 + x * zoom, self.offset.1 + y * zoom)
+    }
+
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        x >= 0 && y >= 0 && x < self.area.width as i32 && y < self.area.height as i32
+    }
+
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        self.offset = (self.offset.0 + dx, self.offset.1 + dy);
+    }
+
+    /// Steps to the next zoom level, keeping the canvas point under `around` in place.
+    pub fn zoom_in(&mut self, around: (i32, i32)) {
+        let zoom = ZOOM_LEVELS
+            .into_iter()
+            .find(|&zoom| zoom > self.zoom)
+            .unwrap_or(self.zoom);
+        self.zoom_at(zoom, around);
+    }
+
+    pub fn zoom_out(&mut self, around: (i32, i32)) {
+        let zoom = ZOOM_LEVELS
+            .into_iter()
+            .rev()
+            .find(|&zoom| zoom < self.zoom)
+            .unwrap_or(self.zoom);
+        self.zoom_at(zoom, around);
+    }
+
+    fn zoom_at(&mut self, zoom: u32, (x, y): (i32, i32)) {
+        let scale = zoom as f32 / self.zoom as f32;
+        let keep = |screen: i32, offset: i32| screen - ((screen - offset) as f32 * scale) as i32;
+        self.offset = (keep(x, self.offset.0), keep(y, self.offset.1));
+        self.zoom = zoom;
+    }
+
+    /// Columns and rows of the canvas that are at least partly inside the area.
+    pub fn visible_cells(&self, canvas: Size) -> (Range<u32>, Range<u32>) {
+        let (left, top) = self.screen_to_cell((0, 0));
+        let (right, bottom) =
+            self.screen_to_cell((self.area.width as i32 - 1, self.area.height as i32 - 1));
+        let clip = |from: i32, to: i32, size: u32| {
+            from.clamp(0, size as i32) as u32..(to + 1).clamp(0, size as i32) as u32
+        };
+        (
+            clip(left, right, canvas.width),
+            clip(top, bottom, canvas.height),
+        )
+    }
+}